@@ -4,6 +4,7 @@ use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
 use rocket::Request;
 
+#[allow(dead_code)]
 pub struct ApiKey<'r>(&'r str);
 
 #[derive(Debug)]
@@ -0,0 +1,352 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::get_adjacent_nodes,
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::{map::walls, request::GameState};
+
+// A single square that, if something sat in it, would leave us shut in a region of
+// `region_size` squares.
+#[derive(Debug, PartialEq)]
+pub struct Doorway {
+    pub gap: Coord,
+    pub region_size: usize,
+}
+
+pub struct SpaceAnalysis {
+    // Free squares reachable from the start, including the start
+    pub area: usize,
+    // Squares whose removal splits the reachable space in two
+    pub articulation_points: Vec<Coord>,
+    // The reachable space split into biconnected regions, which share articulation points
+    pub regions: Vec<Vec<Coord>>,
+    // The articulation point that would shut us into the smallest region
+    pub doorway: Option<Doorway>,
+}
+
+// Builds the graph of free squares reachable from `start` after moving there, and finds its
+// articulation points and biconnected regions with Tarjan's algorithm. Snake bodies are treated
+// as walls, and so is our head since we're leaving it.
+pub fn analyze_move(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    start: &Coord,
+) -> SpaceAnalysis {
+    if !board.contains(start) || walls.contains(start) {
+        return SpaceAnalysis {
+            area: 0,
+            articulation_points: vec![],
+            regions: vec![],
+            doorway: None,
+        };
+    }
+
+    let mut blocked: HashSet<Coord> = board
+        .snakes
+        .iter()
+        .flat_map(|x| x.body.iter().copied())
+        .chain(walls.iter().copied())
+        .collect();
+    blocked.insert(you.head);
+    blocked.remove(start);
+
+    let mut tarjan = Tarjan {
+        board,
+        blocked: &blocked,
+        time: 0,
+        discovered: vec![None; (board.width * board.height) as usize],
+        low: vec![0; (board.width * board.height) as usize],
+        subtree_size: vec![0; (board.width * board.height) as usize],
+        // Squares cut off from the start by each articulation point
+        separated: vec![0; (board.width * board.height) as usize],
+        edges: vec![],
+        regions: vec![],
+    };
+    tarjan.visit(*start, None);
+
+    let area = tarjan.subtree_size[tarjan.index(start)];
+    let mut articulation_points = vec![];
+    let mut doorway: Option<Doorway> = None;
+    for y in 0..board.height as i32 {
+        for x in 0..board.width as i32 {
+            let coord = Coord { x, y };
+            let i = tarjan.index(&coord);
+            let separated = tarjan.separated[i];
+            if separated == 0 {
+                continue;
+            }
+            articulation_points.push(coord);
+            if &coord == start {
+                continue;
+            }
+            let region_size = area - 1 - separated;
+            if doorway.as_ref().is_none_or(|x| region_size < x.region_size) {
+                doorway = Some(Doorway {
+                    gap: coord,
+                    region_size,
+                });
+            }
+        }
+    }
+
+    SpaceAnalysis {
+        area,
+        articulation_points,
+        regions: tarjan.regions,
+        doorway,
+    }
+}
+
+struct Tarjan<'a> {
+    board: &'a Board,
+    blocked: &'a HashSet<Coord>,
+    time: usize,
+    discovered: Vec<Option<usize>>,
+    low: Vec<usize>,
+    subtree_size: Vec<usize>,
+    separated: Vec<usize>,
+    edges: Vec<(Coord, Coord)>,
+    regions: Vec<Vec<Coord>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn index(&self, coord: &Coord) -> usize {
+        (coord.y * self.board.width as i32 + coord.x) as usize
+    }
+
+    fn visit(&mut self, coord: Coord, parent: Option<Coord>) {
+        let i = self.index(&coord);
+        self.discovered[i] = Some(self.time);
+        self.low[i] = self.time;
+        self.subtree_size[i] = 1;
+        self.time += 1;
+        let mut children = 0;
+        let mut separated = 0;
+
+        for adjacent_node in get_adjacent_nodes(&coord) {
+            let next = adjacent_node.coord;
+            if !self.board.contains(&next) || self.blocked.contains(&next) {
+                continue;
+            }
+            let j = self.index(&next);
+            match self.discovered[j] {
+                None => {
+                    children += 1;
+                    self.edges.push((coord, next));
+                    self.visit(next, Some(coord));
+                    self.subtree_size[i] += self.subtree_size[j];
+                    self.low[i] = self.low[i].min(self.low[j]);
+                    if self.low[j] >= self.discovered[i].unwrap_or_default() {
+                        separated += self.subtree_size[j];
+                        self.pop_region(coord, next);
+                    }
+                }
+                Some(discovered) if Some(next) != parent => {
+                    if discovered < self.discovered[i].unwrap_or_default() {
+                        self.edges.push((coord, next));
+                    }
+                    self.low[i] = self.low[i].min(discovered);
+                }
+                _ => {}
+            }
+        }
+
+        // The root only separates anything if it has more than one child, and then from its
+        // own point of view every child but the one we head into is cut off.
+        let is_articulation_point = match parent {
+            Some(_) => separated > 0,
+            None => children > 1,
+        };
+        if is_articulation_point {
+            self.separated[i] = separated;
+        }
+    }
+
+    fn pop_region(&mut self, from: Coord, to: Coord) {
+        let mut region: Vec<Coord> = vec![];
+        while let Some((a, b)) = self.edges.pop() {
+            for coord in [a, b] {
+                if !region.contains(&coord) {
+                    region.push(coord);
+                }
+            }
+            if (a, b) == (from, to) {
+                break;
+            }
+        }
+        self.regions.push(region);
+    }
+}
+
+#[test]
+fn analyze_move_finds_doorway_into_small_room() {
+    // A 2x2 room in the bottom left corner, walled in by us and two opponents, whose only way
+    // out is the gap at (2, 1).
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 6,
+            "width": 6,
+            "food": [],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "my-snake",
+                "name": "My Snake",
+                "health": 54,
+                "body": [{"x": 1, "y": 2}, {"x": 0, "y": 2}, {"x": 0, "y": 3}],
+                "latency": "111",
+                "head": {"x": 1, "y": 2},
+                "length": 3
+              },
+              {
+                "id": "snake-a",
+                "name": "Snake A",
+                "health": 54,
+                "body": [{"x": 2, "y": 0}, {"x": 3, "y": 0}],
+                "latency": "111",
+                "head": {"x": 2, "y": 0},
+                "length": 2
+              },
+              {
+                "id": "snake-b",
+                "name": "Snake B",
+                "health": 54,
+                "body": [{"x": 2, "y": 2}, {"x": 2, "y": 3}],
+                "latency": "111",
+                "head": {"x": 2, "y": 2},
+                "length": 2
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 2}, {"x": 0, "y": 2}, {"x": 0, "y": 3}],
+            "latency": "111",
+            "head": {"x": 1, "y": 2},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    let analysis = analyze_move(
+        &state.board,
+        &state.you,
+        &HashSet::new(),
+        &Coord { x: 1, y: 1 },
+    );
+    assert_eq!(analysis.area, 29);
+    assert!(analysis.articulation_points.contains(&Coord { x: 2, y: 1 }));
+    assert!(analysis.articulation_points.contains(&Coord { x: 3, y: 1 }));
+    assert_eq!(
+        analysis.doorway,
+        Some(Doorway {
+            gap: Coord { x: 2, y: 1 },
+            region_size: 4,
+        })
+    );
+}
+
+#[test]
+fn analyze_move_finds_no_doorway_in_open_field() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 5,
+            "width": 5,
+            "food": [],
+            "hazards": [],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 2, "y": 2}],
+            "latency": "111",
+            "head": {"x": 2, "y": 2},
+            "length": 1
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    let analysis = analyze_move(
+        &state.board,
+        &state.you,
+        &HashSet::new(),
+        &Coord { x: 2, y: 3 },
+    );
+    assert_eq!(analysis.area, 24);
+    assert!(analysis.articulation_points.is_empty());
+    assert_eq!(analysis.regions.len(), 1);
+    assert_eq!(analysis.doorway, None);
+}
+
+#[test]
+fn analyze_move_treats_maze_walls_as_blocked() {
+    // The same room as above, walled in by the maze rather than by opponents.
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {
+            "id": "unique-game-id",
+            "ruleset": {"name": "standard"},
+            "map": "arcade_maze",
+            "timeout": 500
+          },
+          "turn": 0,
+          "board": {
+            "height": 6,
+            "width": 6,
+            "food": [],
+            "hazards": [{"x": 2, "y": 0}, {"x": 3, "y": 0}, {"x": 2, "y": 2}, {"x": 2, "y": 3}],
+            "snakes": [
+              {
+                "id": "my-snake",
+                "name": "My Snake",
+                "health": 54,
+                "body": [{"x": 1, "y": 2}, {"x": 0, "y": 2}, {"x": 0, "y": 3}],
+                "latency": "111",
+                "head": {"x": 1, "y": 2},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 2}, {"x": 0, "y": 2}, {"x": 0, "y": 3}],
+            "latency": "111",
+            "head": {"x": 1, "y": 2},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    let walls = walls(&state.game, &state.board);
+    let analysis = analyze_move(&state.board, &state.you, &walls, &Coord { x: 1, y: 1 });
+    assert_eq!(analysis.area, 29);
+    assert_eq!(
+        analysis.doorway,
+        Some(Doorway {
+            gap: Coord { x: 2, y: 1 },
+            region_size: 4,
+        })
+    );
+    // Moving into a wall goes nowhere
+    let analysis = analyze_move(&state.board, &state.you, &walls, &Coord { x: 2, y: 2 });
+    assert_eq!(analysis.area, 0);
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::get_adjacent_nodes,
+    hazard::HazardLayer,
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+// Extra cost of entering a square, on top of the 1 every step costs. Hazards cost 1 for every
+// few points of damage they do, so 14 damage costs as much as 5 extra steps.
+const DAMAGE_PER_COST: u32 = 3;
+const LARGER_HEAD_COST: usize = 4;
+const EDGE_COST: usize = 1;
+
+// How expensive each square of the board is to move through, so paths can be compared by more
+// than their length. A path through hazard or past a bigger snake's head should lose to a
+// slightly longer path that avoids them. Squares along a wall are as cramped as the edge.
+pub struct CostModel {
+    width: u32,
+    height: u32,
+    costs: Vec<usize>,
+}
+
+impl CostModel {
+    pub fn new(
+        board: &Board,
+        you: &Battlesnake,
+        walls: &HashSet<Coord>,
+        hazards: &HazardLayer,
+    ) -> CostModel {
+        let mut model = CostModel {
+            width: board.width,
+            height: board.height,
+            costs: vec![1; (board.width * board.height) as usize],
+        };
+
+        for x in 0..board.width as i32 {
+            for y in 0..board.height as i32 {
+                let damage = hazards.damage(&Coord { x, y });
+                model.add(&Coord { x, y }, damage.div_ceil(DAMAGE_PER_COST) as usize);
+            }
+        }
+        for opponent in board.snakes.iter().filter(|x| x.id != you.id) {
+            if opponent.length >= you.length {
+                for node in get_adjacent_nodes(&opponent.head) {
+                    model.add(&node.coord, LARGER_HEAD_COST);
+                }
+            }
+        }
+        for x in 0..board.width as i32 {
+            for y in 0..board.height as i32 {
+                let enclosed = get_adjacent_nodes(&Coord { x, y })
+                    .iter()
+                    .any(|node| !board.contains(&node.coord) || walls.contains(&node.coord));
+                if enclosed {
+                    model.add(&Coord { x, y }, EDGE_COST);
+                }
+            }
+        }
+
+        model
+    }
+
+    fn index(&self, coord: &Coord) -> Option<usize> {
+        if coord.x < 0
+            || coord.y < 0
+            || coord.x >= self.width as i32
+            || coord.y >= self.height as i32
+        {
+            return None;
+        }
+        Some((coord.y * self.width as i32 + coord.x) as usize)
+    }
+
+    fn add(&mut self, coord: &Coord, cost: usize) {
+        if let Some(i) = self.index(coord) {
+            self.costs[i] += cost;
+        }
+    }
+
+    // The cost of stepping onto `coord`.
+    pub fn cost(&self, coord: &Coord) -> usize {
+        self.index(coord).map_or(1, |i| self.costs[i])
+    }
+
+    pub fn edge_cost(&self, _from: &Coord, to: &Coord) -> usize {
+        self.cost(to)
+    }
+}
+
+#[test]
+fn cost_model_adds_hazard_head_and_edge_costs() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 11,
+            "width": 11,
+            "food": [],
+            "hazards": [{"x": 3, "y": 3}, {"x": 3, "y": 3}],
+            "snakes": [
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 7, "y": 7}, {"x": 7, "y": 6}, {"x": 7, "y": 5}],
+                "latency": "111",
+                "head": {"x": 7, "y": 7},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 1}, {"x": 1, "y": 2}, {"x": 1, "y": 3}],
+            "latency": "111",
+            "head": {"x": 1, "y": 1},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let hazards = HazardLayer::new(&state.board, 14);
+    let model = CostModel::new(&state.board, &state.you, &HashSet::new(), &hazards);
+
+    assert_eq!(model.cost(&Coord { x: 5, y: 5 }), 1);
+    assert_eq!(
+        model.cost(&Coord { x: 3, y: 3 }),
+        1 + 28usize.div_ceil(DAMAGE_PER_COST as usize)
+    );
+    assert_eq!(model.cost(&Coord { x: 7, y: 8 }), 1 + LARGER_HEAD_COST);
+    assert_eq!(model.cost(&Coord { x: 0, y: 5 }), 1 + EDGE_COST);
+}
@@ -0,0 +1,92 @@
+use crate::{
+    movement_set::{LedgerEntry, Movement, WeightedMovementSet},
+    response::{ExplainResponse, MoveExplanation, ScoreDelta, TierChange},
+};
+
+#[cfg(test)]
+use crate::movement_set::SafetyTier;
+
+// Summarizes the movement set's ledger into a per-move breakdown of which heuristic removed
+// the move, or how each heuristic changed its score and safety tier.
+pub fn explain(set: &WeightedMovementSet, chosen: Movement) -> ExplainResponse {
+    let moves = Movement::all()
+        .iter()
+        .map(|movement| {
+            let mut explanation = MoveExplanation {
+                movement: movement.as_str().to_string(),
+                removed_by: None,
+                removal_reason: None,
+                score_deltas: vec![],
+                tier_changes: vec![],
+                score: set.score(movement),
+                tier: set.tier(movement),
+            };
+            for entry in set.history(movement) {
+                match entry {
+                    LedgerEntry::Adjusted {
+                        source,
+                        delta,
+                        reason,
+                        ..
+                    } => explanation.score_deltas.push(ScoreDelta {
+                        source: source.to_owned(),
+                        delta: *delta,
+                        reason: reason.to_owned(),
+                    }),
+                    LedgerEntry::Classified {
+                        source,
+                        tier,
+                        reason,
+                        ..
+                    } => explanation.tier_changes.push(TierChange {
+                        source: source.to_owned(),
+                        tier: *tier,
+                        reason: reason.to_owned(),
+                    }),
+                    LedgerEntry::Removed { source, reason, .. } => {
+                        explanation.removed_by = Some(source.to_owned());
+                        explanation.removal_reason = Some(reason.to_owned());
+                    }
+                }
+            }
+            explanation
+        })
+        .collect();
+
+    ExplainResponse {
+        chosen_move: chosen.as_str().to_string(),
+        moves,
+    }
+}
+
+#[test]
+fn explain_attributes_removals_and_deltas() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.with_source("remove_up", 1.0, |set| set.remove(&Movement::Up, "wall"));
+    movement_set.with_source("boost_left", 1.0, |set| {
+        set.update_score(&Movement::Left, 20, "food");
+        set.update_score(&Movement::Up, 20, "food");
+    });
+    movement_set.with_source("risk_down", 1.0, |set| {
+        set.classify(&Movement::Down, SafetyTier::Risky, "head-to-head")
+    });
+    let response = explain(&movement_set, movement_set.pick_movement());
+
+    assert_eq!(response.chosen_move, "left");
+    let up = &response.moves[0];
+    assert_eq!(up.removed_by.as_deref(), Some("remove_up"));
+    assert_eq!(up.removal_reason.as_deref(), Some("wall"));
+    assert!(up.score_deltas.is_empty());
+    assert_eq!(up.score, None);
+    assert_eq!(up.tier, SafetyTier::CertainDeath);
+    let left = &response.moves[2];
+    assert_eq!(left.removed_by, None);
+    assert_eq!(left.score_deltas.len(), 1);
+    assert_eq!(left.score_deltas[0].source, "boost_left");
+    assert_eq!(left.score_deltas[0].delta, 20);
+    assert_eq!(left.score_deltas[0].reason, "food");
+    assert_eq!(left.score, Some(120));
+    let down = &response.moves[1];
+    assert_eq!(down.tier, SafetyTier::Risky);
+    assert_eq!(down.tier_changes[0].source, "risk_down");
+}
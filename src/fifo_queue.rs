@@ -18,8 +18,4 @@ impl<T> FifoQueue<T> {
     pub fn dequeue(&mut self) -> Option<T> {
         self.queue.pop_front()
     }
-
-    pub fn clear(&mut self) {
-        self.queue.clear();
-    }
 }
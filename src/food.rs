@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::{distance_map, DistanceMap, Source},
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+// How a race to a piece of food would go.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Contest {
+    // No opponent can get there within a turn of us
+    Uncontested,
+    // We get there first, or at the same time as a smaller snake we'd beat head to head
+    Winning,
+    // An opponent gets there first, or at the same time and survives the collision
+    Losing,
+}
+
+// Every opponent's distance to every square, for deciding which food is worth going for.
+pub struct FoodContest {
+    length: u32,
+    opponents: Vec<(u32, DistanceMap)>,
+}
+
+impl FoodContest {
+    pub fn new(board: &Board, you: &Battlesnake, walls: &HashSet<Coord>) -> FoodContest {
+        let blocked: HashSet<Coord> = board
+            .snakes
+            .iter()
+            .flat_map(|x| x.body.iter().copied())
+            .chain(walls.iter().copied())
+            .collect();
+        let opponents = board
+            .snakes
+            .iter()
+            .filter(|x| x.id != you.id)
+            .map(|opponent| {
+                let map = distance_map(
+                    board.width,
+                    board.height,
+                    &[Source {
+                        coord: opponent.head,
+                        first_move: None,
+                        cost: 0,
+                    }],
+                    |x, _| !blocked.contains(x),
+                    |_, _| 1,
+                    |_, _| false,
+                );
+                (opponent.length, map)
+            })
+            .collect();
+        FoodContest {
+            length: you.length,
+            opponents,
+        }
+    }
+
+    // The number of moves the nearest opponent needs to get to `food`, if any can.
+    pub fn nearest_opponent(&self, food: &Coord) -> Option<usize> {
+        self.opponents
+            .iter()
+            .filter_map(|(_, map)| map.distance(food))
+            .min()
+    }
+
+    // Who wins the race to `food` when we need `steps` moves to get there.
+    pub fn contest(&self, food: &Coord, steps: usize) -> Contest {
+        let mut contest = Contest::Uncontested;
+        for (length, map) in &self.opponents {
+            let their_steps = match map.distance(food) {
+                Some(x) => x,
+                None => continue,
+            };
+            if their_steps < steps || (their_steps == steps && *length >= self.length) {
+                return Contest::Losing;
+            }
+            if their_steps <= steps + 1 {
+                contest = Contest::Winning;
+            }
+        }
+        contest
+    }
+}
+
+#[test]
+fn contest_compares_distance_and_length() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 11,
+            "width": 11,
+            "food": [],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "my-snake",
+                "name": "My Snake",
+                "health": 54,
+                "body": [{"x": 0, "y": 0}, {"x": 0, "y": 1}, {"x": 0, "y": 2}],
+                "latency": "111",
+                "head": {"x": 0, "y": 0},
+                "length": 3
+              },
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 6, "y": 0}, {"x": 6, "y": 1}, {"x": 6, "y": 2}],
+                "latency": "111",
+                "head": {"x": 6, "y": 0},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 0, "y": 0}, {"x": 0, "y": 1}, {"x": 0, "y": 2}],
+            "latency": "111",
+            "head": {"x": 0, "y": 0},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let contest = FoodContest::new(&state.board, &state.you, &HashSet::new());
+
+    assert_eq!(contest.nearest_opponent(&Coord { x: 3, y: 0 }), Some(3));
+    // Both three moves away and the same length, so we'd both die
+    assert_eq!(contest.contest(&Coord { x: 3, y: 0 }, 3), Contest::Losing);
+    assert_eq!(
+        contest.contest(&Coord { x: 2, y: 0 }, 2),
+        Contest::Uncontested
+    );
+    // Taking the long way round lets them get close enough to contest it
+    assert_eq!(contest.contest(&Coord { x: 2, y: 0 }, 3), Contest::Winning);
+    assert_eq!(contest.contest(&Coord { x: 5, y: 0 }, 5), Contest::Losing);
+}
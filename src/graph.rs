@@ -1,3 +1,6 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use crate::{movement_set::Movement, request::Coord};
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy)]
@@ -6,11 +9,6 @@ pub struct Node {
     pub movement: Movement,
 }
 
-pub struct LeafNode {
-    pub node: Node,
-    pub parent: Node,
-}
-
 pub fn get_adjacent_nodes(coord: &Coord) -> Vec<Node> {
     vec![
         Node {
@@ -43,3 +41,404 @@ pub fn get_adjacent_nodes(coord: &Coord) -> Vec<Node> {
         },
     ]
 }
+
+// A starting point for a search. Sources are always reached, whether or not they are passable.
+// Everything reached from a source inherits its `first_move`, so searches seeded from the squares
+// around our head can tell which move leads where. `cost` is what it took to get to the source,
+// and is counted in the distance to everything reached from it.
+#[derive(Clone, Copy)]
+pub struct Source {
+    pub coord: Coord,
+    pub first_move: Option<Movement>,
+    pub cost: usize,
+}
+
+// The squares next to `head` that can be reached with one of `moves`, each costing as much as
+// the step onto it.
+pub fn sources_from_moves<F, C>(head: &Coord, moves: F, mut edge_cost: C) -> Vec<Source>
+where
+    F: Fn(&Movement) -> bool,
+    C: FnMut(&Coord, &Coord) -> usize,
+{
+    get_adjacent_nodes(head)
+        .into_iter()
+        .filter(|x| moves(&x.movement))
+        .map(|x| Source {
+            coord: x.coord,
+            first_move: Some(x.movement),
+            cost: edge_cost(head, &x.coord).max(1),
+        })
+        .collect()
+}
+
+pub struct DistanceMap {
+    width: u32,
+    height: u32,
+    distances: Vec<Option<usize>>,
+    // Moves from the source along the cheapest path, which is the distance when steps cost 1
+    steps: Vec<usize>,
+    first_moves: Vec<Option<Movement>>,
+    parents: Vec<Option<Coord>>,
+    reached: Vec<Coord>,
+}
+
+impl DistanceMap {
+    fn index(&self, coord: &Coord) -> Option<usize> {
+        if coord.x < 0
+            || coord.y < 0
+            || coord.x >= self.width as i32
+            || coord.y >= self.height as i32
+        {
+            return None;
+        }
+        Some((coord.y * self.width as i32 + coord.x) as usize)
+    }
+
+    pub fn distance(&self, coord: &Coord) -> Option<usize> {
+        self.index(coord).and_then(|i| self.distances[i])
+    }
+
+    pub fn first_move(&self, coord: &Coord) -> Option<Movement> {
+        self.index(coord).and_then(|i| self.first_moves[i])
+    }
+
+    // The square this one was reached from, if it wasn't a source.
+    pub fn parent(&self, coord: &Coord) -> Option<Coord> {
+        self.index(coord).and_then(|i| self.parents[i])
+    }
+
+    // Every square reached, nearest first.
+    pub fn reached(&self) -> &[Coord] {
+        &self.reached
+    }
+
+    // The squares along the way to `coord`, starting with the source it was reached from.
+    pub fn path_to(&self, coord: &Coord) -> Vec<Coord> {
+        let mut path = vec![*coord];
+        while let Some(parent) = self.parent(&path[path.len() - 1]) {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+}
+
+// Finds the distance from the nearest source to every square on a `width` by `height` board.
+//
+// - `passable(coord, steps)` decides whether a square can be entered that many moves after the
+//   sources, so squares can open up over time. Moves are counted whatever they cost.
+// - `edge_cost(from, to)` is the cost of a single step, at least 1.
+// - `stop(coord, distance)` ends the search as soon as it returns true for a reached square.
+//
+// Squares are settled in order of distance, ties broken first come first served, so with unit
+// costs this is a plain breadth first search.
+pub fn distance_map<P, C, S>(
+    width: u32,
+    height: u32,
+    sources: &[Source],
+    passable: P,
+    edge_cost: C,
+    stop: S,
+) -> DistanceMap
+where
+    P: FnMut(&Coord, usize) -> bool,
+    C: FnMut(&Coord, &Coord) -> usize,
+    S: FnMut(&Coord, usize) -> bool,
+{
+    search(width, height, sources, passable, edge_cost, stop, |_| 0)
+}
+
+pub struct Path {
+    pub coords: Vec<Coord>,
+    pub cost: usize,
+    pub first_move: Option<Movement>,
+}
+
+// Finds the cheapest path from any source to `goal` with A*, using the Manhattan distance to
+// the goal as the heuristic. Since every step costs at least 1 this never overestimates, so the
+// path found is as cheap as the one `distance_map` would find.
+pub fn find_path<P, C>(
+    width: u32,
+    height: u32,
+    sources: &[Source],
+    goal: &Coord,
+    passable: P,
+    edge_cost: C,
+) -> Option<Path>
+where
+    P: FnMut(&Coord, usize) -> bool,
+    C: FnMut(&Coord, &Coord) -> usize,
+{
+    let map = search(
+        width,
+        height,
+        sources,
+        passable,
+        edge_cost,
+        |x, _| x == goal,
+        |x| manhattan_distance(x, goal),
+    );
+    let cost = map.distance(goal)?;
+    Some(Path {
+        coords: map.path_to(goal),
+        cost,
+        first_move: map.first_move(goal),
+    })
+}
+
+pub fn manhattan_distance(a: &Coord, b: &Coord) -> usize {
+    ((a.x - b.x).abs() + (a.y - b.y).abs()) as usize
+}
+
+fn search<P, C, S, H>(
+    width: u32,
+    height: u32,
+    sources: &[Source],
+    mut passable: P,
+    mut edge_cost: C,
+    mut stop: S,
+    heuristic: H,
+) -> DistanceMap
+where
+    P: FnMut(&Coord, usize) -> bool,
+    C: FnMut(&Coord, &Coord) -> usize,
+    S: FnMut(&Coord, usize) -> bool,
+    H: Fn(&Coord) -> usize,
+{
+    let size = (width * height) as usize;
+    let mut map = DistanceMap {
+        width,
+        height,
+        distances: vec![None; size],
+        steps: vec![0; size],
+        first_moves: vec![None; size],
+        parents: vec![None; size],
+        reached: vec![],
+    };
+    let mut settled = vec![false; size];
+    // Ordered by estimated total cost, then by insertion order.
+    let mut frontier = BinaryHeap::new();
+    let mut sequence = 0;
+
+    for source in sources {
+        if let Some(i) = map.index(&source.coord) {
+            if map.distances[i].is_none() {
+                map.distances[i] = Some(source.cost);
+                map.first_moves[i] = source.first_move;
+                frontier.push(Reverse((
+                    source.cost + heuristic(&source.coord),
+                    sequence,
+                    i,
+                )));
+                sequence += 1;
+            }
+        }
+    }
+
+    while let Some(Reverse((_, _, i))) = frontier.pop() {
+        if settled[i] {
+            continue;
+        }
+        let coord = Coord {
+            x: (i % width as usize) as i32,
+            y: (i / width as usize) as i32,
+        };
+        let distance = match map.distances[i] {
+            Some(x) => x,
+            None => continue,
+        };
+        settled[i] = true;
+        map.reached.push(coord);
+        if stop(&coord, distance) {
+            break;
+        }
+
+        for adjacent_node in get_adjacent_nodes(&coord) {
+            let next = match map.index(&adjacent_node.coord) {
+                Some(x) if !settled[x] => x,
+                _ => continue,
+            };
+            let next_distance = distance + edge_cost(&coord, &adjacent_node.coord).max(1);
+            let next_steps = map.steps[i] + 1;
+            if map.distances[next].is_some_and(|x| x <= next_distance)
+                || !passable(&adjacent_node.coord, next_steps)
+            {
+                continue;
+            }
+            map.distances[next] = Some(next_distance);
+            map.steps[next] = next_steps;
+            map.first_moves[next] = map.first_moves[i];
+            map.parents[next] = Some(coord);
+            frontier.push(Reverse((
+                next_distance + heuristic(&adjacent_node.coord),
+                sequence,
+                next,
+            )));
+            sequence += 1;
+        }
+    }
+
+    map
+}
+
+#[cfg(test)]
+fn coord(x: i32, y: i32) -> Coord {
+    Coord { x, y }
+}
+
+#[test]
+fn distance_map_finds_shortest_distances_and_first_moves() {
+    let head = coord(1, 1);
+    let wall = [coord(2, 1), coord(2, 2)];
+    let map = distance_map(
+        4,
+        4,
+        &sources_from_moves(
+            &head,
+            |x| x == &Movement::Up || x == &Movement::Down,
+            |_, _| 1,
+        ),
+        |x, _| x != &head && !wall.contains(x),
+        |_, _| 1,
+        |_, _| false,
+    );
+
+    assert_eq!(map.distance(&coord(1, 2)), Some(1));
+    assert_eq!(map.first_move(&coord(1, 2)), Some(Movement::Up));
+    assert_eq!(map.distance(&coord(0, 1)), Some(3));
+    assert_eq!(map.first_move(&coord(0, 1)), Some(Movement::Up));
+    assert_eq!(map.distance(&coord(3, 1)), Some(4));
+    assert_eq!(map.first_move(&coord(3, 1)), Some(Movement::Down));
+    assert_eq!(map.distance(&coord(2, 1)), None);
+    assert_eq!(map.distance(&head), None);
+    assert_eq!(map.reached().len(), 13);
+}
+
+#[test]
+fn distance_map_stops_early() {
+    let map = distance_map(
+        5,
+        5,
+        &[Source {
+            coord: coord(0, 0),
+            first_move: None,
+            cost: 0,
+        }],
+        |_, _| true,
+        |_, _| 1,
+        |x, _| x == &coord(1, 0),
+    );
+
+    assert_eq!(map.reached().last(), Some(&coord(1, 0)));
+    assert!(map.reached().len() < 25);
+}
+
+#[test]
+fn distance_map_uses_edge_costs() {
+    let expensive = coord(1, 0);
+    let map = distance_map(
+        3,
+        2,
+        &[Source {
+            coord: coord(0, 0),
+            first_move: None,
+            cost: 0,
+        }],
+        |_, _| true,
+        |_, to| if to == &expensive { 5 } else { 1 },
+        |_, _| false,
+    );
+
+    assert_eq!(map.distance(&coord(2, 0)), Some(4));
+    assert_eq!(map.distance(&expensive), Some(5));
+}
+
+#[test]
+fn distance_map_passes_moves_rather_than_cost_to_passable() {
+    let gate = coord(1, 0);
+    let map = distance_map(
+        3,
+        1,
+        &[Source {
+            coord: coord(0, 0),
+            first_move: None,
+            cost: 0,
+        }],
+        |x, steps| x != &gate || steps >= 2,
+        |_, _| 5,
+        |_, _| false,
+    );
+
+    assert_eq!(map.distance(&coord(2, 0)), None);
+}
+
+#[test]
+fn sources_from_moves_charge_the_first_step() {
+    let head = coord(1, 0);
+    let hazard = coord(0, 0);
+    let map = distance_map(
+        3,
+        2,
+        &sources_from_moves(&head, |_| true, |_, to| if to == &hazard { 5 } else { 1 }),
+        |x, _| x != &head,
+        |_, to| if to == &hazard { 5 } else { 1 },
+        |_, _| false,
+    );
+
+    // Going round is cheaper than stepping straight into the hazard
+    assert_eq!(map.distance(&hazard), Some(5));
+    assert_eq!(map.distance(&coord(0, 1)), Some(2));
+    assert_eq!(map.first_move(&coord(0, 1)), Some(Movement::Up));
+}
+
+#[test]
+fn find_path_avoids_expensive_squares() {
+    let expensive = [coord(1, 0), coord(1, 1)];
+    let path = find_path(
+        3,
+        3,
+        &[Source {
+            coord: coord(0, 0),
+            first_move: Some(Movement::Up),
+            cost: 0,
+        }],
+        &coord(2, 0),
+        |_, _| true,
+        |_, to| if expensive.contains(to) { 10 } else { 1 },
+    )
+    .expect("expected a path");
+
+    assert_eq!(path.cost, 6);
+    assert_eq!(
+        path.coords,
+        vec![
+            coord(0, 0),
+            coord(0, 1),
+            coord(0, 2),
+            coord(1, 2),
+            coord(2, 2),
+            coord(2, 1),
+            coord(2, 0)
+        ]
+    );
+    assert_eq!(path.first_move, Some(Movement::Up));
+}
+
+#[test]
+fn find_path_returns_none_when_blocked() {
+    let wall = [coord(1, 0), coord(1, 1), coord(1, 2)];
+    let path = find_path(
+        3,
+        3,
+        &[Source {
+            coord: coord(0, 0),
+            first_move: None,
+            cost: 0,
+        }],
+        &coord(2, 0),
+        |x, _| !wall.contains(x),
+        |_, _| 1,
+    );
+
+    assert!(path.is_none());
+}
@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use crate::request::{Board, Coord};
+
+// How much damage each square of the board does to a snake ending its turn there. Maps like
+// sinkholes list the same square several times to stack damage, and each listing adds another
+// `damage_per_turn`.
+#[derive(Clone)]
+pub struct HazardLayer {
+    width: u32,
+    height: u32,
+    stacks: Vec<u32>,
+    damage_per_turn: u32,
+    food: HashSet<Coord>,
+}
+
+impl HazardLayer {
+    pub fn new(board: &Board, damage_per_turn: u32) -> HazardLayer {
+        let mut layer = HazardLayer {
+            width: board.width,
+            height: board.height,
+            stacks: vec![0; (board.width * board.height) as usize],
+            damage_per_turn,
+            food: board.food.iter().copied().collect(),
+        };
+        for hazard in &board.hazards {
+            if let Some(i) = layer.index(hazard) {
+                layer.stacks[i] += 1;
+            }
+        }
+        layer
+    }
+
+    fn index(&self, coord: &Coord) -> Option<usize> {
+        if coord.x < 0
+            || coord.y < 0
+            || coord.x >= self.width as i32
+            || coord.y >= self.height as i32
+        {
+            return None;
+        }
+        Some((coord.y * self.width as i32 + coord.x) as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.iter().all(|x| *x == 0)
+    }
+
+    pub fn stacks(&self, coord: &Coord) -> u32 {
+        self.index(coord).map_or(0, |i| self.stacks[i])
+    }
+
+    // Hazard damage for ending a turn on `coord`, not counting the 1 health every turn costs.
+    pub fn damage(&self, coord: &Coord) -> u32 {
+        self.stacks(coord) * self.damage_per_turn
+    }
+
+    // The health lost by moving into `coord`. Snakes that eat don't take hazard damage, so a food
+    // square only ever costs 1.
+    pub fn health_cost(&self, coord: &Coord) -> u32 {
+        if self.food.contains(coord) {
+            return 1;
+        }
+        1 + self.damage(coord)
+    }
+
+    // Our health after following `path` from `health`, or None if we'd die on the way.
+    pub fn health_after(&self, health: u32, path: &[Coord]) -> Option<u32> {
+        let mut health = health;
+        for coord in path {
+            health = if self.food.contains(coord) {
+                100
+            } else {
+                health
+                    .checked_sub(self.health_cost(coord))
+                    .filter(|x| *x > 0)?
+            };
+        }
+        Some(health)
+    }
+}
+
+#[test]
+fn hazard_layer_stacks_damage() {
+    let board: Board = serde_json::from_str(
+        r#"{
+          "height": 5,
+          "width": 5,
+          "food": [{"x": 0, "y": 2}],
+          "hazards": [{"x": 0, "y": 0}, {"x": 0, "y": 1}, {"x": 0, "y": 1}, {"x": 0, "y": 2}],
+          "snakes": []
+        }"#,
+    )
+    .expect("failed to parse board");
+    let layer = HazardLayer::new(&board, 14);
+
+    assert_eq!(layer.stacks(&Coord { x: 0, y: 1 }), 2);
+    assert_eq!(layer.damage(&Coord { x: 0, y: 1 }), 28);
+    assert_eq!(layer.health_cost(&Coord { x: 0, y: 1 }), 29);
+    // Eating on a hazard skips its damage
+    assert_eq!(layer.health_cost(&Coord { x: 0, y: 2 }), 1);
+
+    let path = [Coord { x: 0, y: 0 }, Coord { x: 0, y: 1 }];
+    assert_eq!(layer.health_after(50, &path), Some(6));
+    assert_eq!(layer.health_after(44, &path), None);
+    let path = [
+        Coord { x: 0, y: 0 },
+        Coord { x: 0, y: 1 },
+        Coord { x: 0, y: 2 },
+    ];
+    assert_eq!(layer.health_after(50, &path), Some(100));
+}
@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+
+use crate::{
+    hazard::HazardLayer,
+    logic::{
+        avoid_bounds, avoid_chokepoints, avoid_hazards, avoid_small_spaces, avoid_snake_bodies,
+        avoid_starvation, avoid_walls, fill_space, follow_cycle, handle_opponent_heads, scan_food,
+        scan_tail, trap_opponents,
+    },
+    map::walls,
+    movement_set::WeightedMovementSet,
+    request::{Battlesnake, Board, Coord, Game},
+    solo::HamiltonianCycle,
+    survival,
+};
+
+// Everything a heuristic may look at when scoring a turn.
+pub struct Context<'a> {
+    pub game: &'a Game,
+    pub turn: u32,
+    pub board: &'a Board,
+    pub you: &'a Battlesnake,
+    // Hazards the map makes impassable
+    pub walls: HashSet<Coord>,
+    pub hazards: HazardLayer,
+    // The route round the board to follow in solo games, if the board has one
+    pub cycle: Option<HamiltonianCycle>,
+    // Set when scoring a turn for `/explain` rather than playing it, so nothing kept between
+    // turns is changed
+    pub explaining: bool,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(game: &'a Game, turn: &u32, board: &'a Board, you: &'a Battlesnake) -> Context<'a> {
+        let walls = walls(game, board);
+        let cycle = if game.ruleset.name == "solo" {
+            HamiltonianCycle::new(board.width, board.height, &walls)
+        } else {
+            None
+        };
+        Context {
+            game,
+            turn: *turn,
+            board,
+            you,
+            walls,
+            hazards: HazardLayer::new(board, game.ruleset.settings.hazard_damage_per_turn),
+            cycle,
+            explaining: false,
+        }
+    }
+}
+
+// One step of a scoring pipeline. Score changes made in `apply` are scaled by `weight`, removals
+// and safety tiers are not.
+pub trait Heuristic: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn weight(&self) -> f64 {
+        1.0
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet);
+}
+
+impl Heuristic for Box<dyn Heuristic> {
+    fn name(&self) -> &str {
+        self.as_ref().name()
+    }
+
+    fn weight(&self) -> f64 {
+        self.as_ref().weight()
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        self.as_ref().apply(context, set)
+    }
+}
+
+// Overrides the weight of another heuristic.
+pub struct Weighted<H> {
+    pub heuristic: H,
+    pub weight: f64,
+}
+
+impl<H: Heuristic> Heuristic for Weighted<H> {
+    fn name(&self) -> &str {
+        self.heuristic.name()
+    }
+
+    fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        self.heuristic.apply(context, set)
+    }
+}
+
+pub struct AvoidBounds;
+
+impl Heuristic for AvoidBounds {
+    fn name(&self) -> &str {
+        "avoid_bounds"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_bounds(context.board.width, context.board.height, context.you, set)
+    }
+}
+
+pub struct AvoidWalls;
+
+impl Heuristic for AvoidWalls {
+    fn name(&self) -> &str {
+        "avoid_walls"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_walls(&context.walls, context.you, set)
+    }
+}
+
+pub struct AvoidSnakeBodies;
+
+impl Heuristic for AvoidSnakeBodies {
+    fn name(&self) -> &str {
+        "avoid_snake_bodies"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_snake_bodies(&context.board.snakes, context.you, set)
+    }
+}
+
+pub struct AvoidHazards;
+
+impl Heuristic for AvoidHazards {
+    fn name(&self) -> &str {
+        "avoid_hazards"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_hazards(&context.hazards, context.you, set)
+    }
+}
+
+pub struct HandleOpponentHeads;
+
+impl Heuristic for HandleOpponentHeads {
+    fn name(&self) -> &str {
+        "handle_opponent_heads"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        handle_opponent_heads(context.board, context.you, &context.walls, set)
+    }
+}
+
+pub struct ScanFood;
+
+impl Heuristic for ScanFood {
+    fn name(&self) -> &str {
+        "scan_food"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        scan_food(
+            context.board,
+            context.you,
+            &context.walls,
+            &context.hazards,
+            set,
+        )
+    }
+}
+
+pub struct AvoidStarvation;
+
+impl Heuristic for AvoidStarvation {
+    fn name(&self) -> &str {
+        "avoid_starvation"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_starvation(
+            context.board,
+            context.you,
+            &context.walls,
+            &context.hazards,
+            set,
+        )
+    }
+}
+
+pub struct AvoidSmallSpaces;
+
+impl Heuristic for AvoidSmallSpaces {
+    fn name(&self) -> &str {
+        "avoid_small_spaces"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_small_spaces(context.board, context.you, &context.walls, set)
+    }
+}
+
+pub struct AvoidChokepoints;
+
+impl Heuristic for AvoidChokepoints {
+    fn name(&self) -> &str {
+        "avoid_chokepoints"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        avoid_chokepoints(context.board, context.you, &context.walls, set)
+    }
+}
+
+pub struct FillSpace;
+
+impl Heuristic for FillSpace {
+    fn name(&self) -> &str {
+        "fill_space"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        let plan = if context.explaining {
+            survival::peek_square
+        } else {
+            survival::next_square
+        };
+        let next = plan(
+            &context.game.id,
+            context.turn,
+            context.board,
+            context.you,
+            &context.walls,
+            &context.hazards,
+        );
+        fill_space(context.you, next, set)
+    }
+}
+
+pub struct TrapOpponents;
+
+impl Heuristic for TrapOpponents {
+    fn name(&self) -> &str {
+        "trap_opponents"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        trap_opponents(context.board, context.you, &context.walls, set)
+    }
+}
+
+pub struct FollowCycle;
+
+impl Heuristic for FollowCycle {
+    fn name(&self) -> &str {
+        "follow_cycle"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        follow_cycle(context.board, context.you, context.cycle.as_ref(), set)
+    }
+}
+
+pub struct ScanTail;
+
+impl Heuristic for ScanTail {
+    fn name(&self) -> &str {
+        "scan_tail"
+    }
+
+    fn apply(&self, context: &Context, set: &mut WeightedMovementSet) {
+        scan_tail(
+            context.board,
+            context.you,
+            &context.walls,
+            &context.hazards,
+            set,
+        )
+    }
+}
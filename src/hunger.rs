@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::{distance_map, get_adjacent_nodes, Source},
+    hazard::HazardLayer,
+    movement_set::Movement,
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+// Health to spare on the way to the nearest food before we drop everything to go and eat.
+const STARVATION_MARGIN: u32 = 10;
+const HUNGRY_HEALTH: u32 = 50;
+const FULL_HEALTH: u32 = 75;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Hunger {
+    // Healthy and already longer than everyone, so growing only takes up space
+    Full,
+    Normal,
+    Hungry,
+    // The nearest food is barely within reach
+    Starving,
+}
+
+impl Hunger {
+    // How much food seeking bonuses are multiplied by.
+    pub fn appetite(&self) -> isize {
+        match self {
+            Hunger::Full => 0,
+            Hunger::Normal => 1,
+            Hunger::Hungry => 2,
+            Hunger::Starving => 3,
+        }
+    }
+}
+
+// For each of `moves`, the health it costs to get to the nearest food that way, or None if there's
+// no food that way at all.
+pub fn food_costs<F>(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+    moves: F,
+) -> Vec<(Movement, Option<u32>)>
+where
+    F: Fn(&Movement) -> bool,
+{
+    let snake_coords: HashSet<Coord> = board
+        .snakes
+        .iter()
+        .flat_map(|x| x.body.iter().copied())
+        .chain(walls.iter().copied())
+        .collect();
+    get_adjacent_nodes(&you.head)
+        .into_iter()
+        .filter(|x| moves(&x.movement))
+        .map(|node| {
+            let map = distance_map(
+                board.width,
+                board.height,
+                &[Source {
+                    coord: node.coord,
+                    first_move: Some(node.movement),
+                    cost: hazards.health_cost(&node.coord) as usize,
+                }],
+                |x, _| !snake_coords.contains(x),
+                |_, to| hazards.health_cost(to) as usize,
+                |x, _| board.food.contains(x),
+            );
+            let cost = map
+                .reached()
+                .last()
+                .filter(|x| board.food.contains(x))
+                .and_then(|x| map.distance(x))
+                .map(|x| x as u32);
+            (node.movement, cost)
+        })
+        .collect()
+}
+
+pub fn hunger(board: &Board, you: &Battlesnake, costs: &[(Movement, Option<u32>)]) -> Hunger {
+    let nearest = costs.iter().filter_map(|x| x.1).min();
+    if let Some(cost) = nearest {
+        if you.health <= cost + STARVATION_MARGIN {
+            return Hunger::Starving;
+        }
+    }
+    if you.health <= HUNGRY_HEALTH {
+        return Hunger::Hungry;
+    }
+    let longest = board
+        .snakes
+        .iter()
+        .filter(|x| x.id != you.id)
+        .all(|x| x.length < you.length);
+    if you.health >= FULL_HEALTH && longest {
+        return Hunger::Full;
+    }
+    Hunger::Normal
+}
+
+#[test]
+fn food_costs_include_hazard_damage() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "royale"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 5,
+            "width": 5,
+            "food": [{"x": 2, "y": 4}],
+            "hazards": [{"x": 2, "y": 3}, {"x": 2, "y": 3}],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 40,
+            "body": [{"x": 2, "y": 2}, {"x": 2, "y": 1}, {"x": 2, "y": 0}],
+            "latency": "111",
+            "head": {"x": 2, "y": 2},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    let hazards = HazardLayer::new(
+        &state.board,
+        state.game.ruleset.settings.hazard_damage_per_turn,
+    );
+    let costs = food_costs(&state.board, &state.you, &HashSet::new(), &hazards, |x| {
+        x != &Movement::Down
+    });
+    // Straight through the doubly stacked hazard costs 1 + 28, then 1 to eat
+    assert!(costs.contains(&(Movement::Up, Some(30))));
+    // Around it costs 4 moves
+    assert!(costs.contains(&(Movement::Left, Some(4))));
+    assert_eq!(hunger(&state.board, &state.you, &costs), Hunger::Hungry);
+
+    let mut you = state.you.clone();
+    you.health = 12;
+    assert_eq!(hunger(&state.board, &you, &costs), Hunger::Starving);
+}
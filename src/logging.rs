@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::Write;
+
+use log::{Log, Metadata, Record};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::request::GameState;
+
+// Every record logged while handling a request carries this, so lines from concurrent games can
+// be told apart.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LogContext {
+    pub snake: String,
+    pub game_id: String,
+    pub turn: u32,
+    pub request_id: String,
+}
+
+impl LogContext {
+    pub fn new(snake: &str, state: &GameState) -> LogContext {
+        LogContext {
+            snake: snake.to_string(),
+            game_id: state.game.id.to_owned(),
+            turn: state.turn,
+            request_id: format!("{:08x}", rand::random::<u32>()),
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<LogContext>> = const { RefCell::new(None) };
+}
+
+struct ContextGuard {
+    previous: Option<LogContext>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CONTEXT.with(|x| *x.borrow_mut() = previous);
+    }
+}
+
+pub fn with_context<T, F>(context: LogContext, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let previous = CONTEXT.with(|x| x.borrow_mut().replace(context));
+    let _guard = ContextGuard { previous };
+    f()
+}
+
+pub fn current_context() -> Option<LogContext> {
+    CONTEXT.with(|x| x.borrow().clone())
+}
+
+// Sets up the logger from the environment:
+// - `RUST_LOG` sets verbosity per module, e.g. `info,battle_snake_rust::movement_set=debug`
+// - `LOG_FORMAT=json` writes one JSON object per line instead of plain text
+// - `LOG_DEBUG_GAME=<game id>` logs everything at `LOG_DEBUG_FILTER` (default `debug`) for
+//   that one game, regardless of `RUST_LOG`
+pub fn init() {
+    let json = env::var("LOG_FORMAT")
+        .map(|x| x.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    let filters = env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+    let debug = env::var("LOG_DEBUG_GAME").ok().map(|game_id| {
+        let filters = env::var("LOG_DEBUG_FILTER").unwrap_or_else(|_| "debug".to_string());
+        (game_id, build_logger(&filters, json))
+    });
+
+    let logger = ContextLogger {
+        default: build_logger(&filters, json),
+        debug,
+    };
+    let max_level = match &logger.debug {
+        Some((_, debug)) => logger.default.filter().max(debug.filter()),
+        None => logger.default.filter(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+fn build_logger(filters: &str, json: bool) -> env_logger::Logger {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(filters);
+    if json {
+        builder.format(|buf, record| {
+            let line = json_record(
+                record,
+                &buf.timestamp().to_string(),
+                current_context().as_ref(),
+            );
+            writeln!(buf, "{}", line)
+        });
+    } else {
+        builder.format(|buf, record| {
+            let context = match current_context() {
+                Some(x) => format!(
+                    " {} game={} turn={} req={}",
+                    x.snake, x.game_id, x.turn, x.request_id
+                ),
+                None => String::new(),
+            };
+            writeln!(
+                buf,
+                "[{} {:<5} {}{}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                context,
+                record.args()
+            )
+        });
+    }
+    builder.build()
+}
+
+fn json_record(record: &Record, timestamp: &str, context: Option<&LogContext>) -> Value {
+    let mut line = json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let (Some(fields), Some(Value::Object(context))) =
+        (line.as_object_mut(), context.map(|x| json!(x)))
+    {
+        fields.extend(context);
+    }
+    line
+}
+
+struct ContextLogger {
+    default: env_logger::Logger,
+    debug: Option<(String, env_logger::Logger)>,
+}
+
+impl ContextLogger {
+    fn debug_logger(&self) -> Option<&env_logger::Logger> {
+        let (game_id, logger) = self.debug.as_ref()?;
+        match current_context() {
+            Some(context) if &context.game_id == game_id => Some(logger),
+            _ => None,
+        }
+    }
+}
+
+impl Log for ContextLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.default.enabled(metadata)
+            || matches!(self.debug_logger(), Some(x) if x.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(debug) = self.debug_logger() {
+            if debug.matches(record) {
+                debug.log(record);
+                return;
+            }
+        }
+        self.default.log(record);
+    }
+
+    fn flush(&self) {
+        self.default.flush();
+    }
+}
+
+#[test]
+fn with_context_restores_previous_context() {
+    let outer = LogContext {
+        snake: "rusty".to_string(),
+        game_id: "game-1".to_string(),
+        turn: 1,
+        request_id: "00000001".to_string(),
+    };
+    let inner = LogContext {
+        game_id: "game-2".to_string(),
+        ..outer.clone()
+    };
+    with_context(outer.clone(), || {
+        with_context(inner.clone(), || {
+            assert_eq!(current_context(), Some(inner.clone()));
+        });
+        assert_eq!(current_context(), Some(outer.clone()));
+    });
+    assert_eq!(current_context(), None);
+}
+
+#[test]
+fn json_record_includes_context() {
+    let context = LogContext {
+        snake: "rusty".to_string(),
+        game_id: "game-1".to_string(),
+        turn: 7,
+        request_id: "0000abcd".to_string(),
+    };
+    let line = json_record(
+        &Record::builder()
+            .args(format_args!("Safe moves: up"))
+            .level(log::Level::Info)
+            .target("battle_snake_rust::snakes::rusty")
+            .build(),
+        "2021-01-01T00:00:00Z",
+        Some(&context),
+    );
+    assert_eq!(line["message"], "Safe moves: up");
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["game_id"], "game-1");
+    assert_eq!(line["turn"], 7);
+    assert_eq!(line["request_id"], "0000abcd");
+}
@@ -1,10 +1,18 @@
 use std::collections::HashSet;
 
 use crate::{
+    chokepoints::analyze_move,
+    cost_model::CostModel,
     fifo_queue::FifoQueue,
-    graph::{get_adjacent_nodes, LeafNode},
-    movement_set::{Movement, WeightedMovementSet},
+    food::{Contest, FoodContest},
+    graph::{distance_map, find_path, get_adjacent_nodes, manhattan_distance, sources_from_moves},
+    hazard::HazardLayer,
+    hunger::{food_costs, hunger, Hunger},
+    movement_set::{Movement, SafetyTier, WeightedMovementSet},
     request::{Battlesnake, Board, Coord},
+    solo::{solo_move, HamiltonianCycle},
+    space::{area_from_head, reachable_area, space_after_eating, VacateTimes},
+    threat::{blocked_squares, threats},
 };
 
 pub fn avoid_bounds(width: u32, height: u32, you: &Battlesnake, set: &mut WeightedMovementSet) {
@@ -17,13 +25,26 @@ pub fn avoid_bounds(width: u32, height: u32, you: &Battlesnake, set: &mut Weight
             || node.coord.y < 0
             || node.coord.y >= (height as i32)
         {
-            set.remove(&node.movement);
+            set.remove(&node.movement, "out of bounds");
+        }
+    }
+}
+
+pub fn avoid_walls(walls: &HashSet<Coord>, you: &Battlesnake, set: &mut WeightedMovementSet) {
+    if walls.is_empty() {
+        return;
+    }
+
+    info!("Avoiding walls");
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        if walls.contains(&adjacent_node.coord) {
+            set.remove(&adjacent_node.movement, "wall");
         }
     }
 }
 
 pub fn avoid_snake_bodies(
-    snakes: &Vec<Battlesnake>,
+    snakes: &[Battlesnake],
     you: &Battlesnake,
     set: &mut WeightedMovementSet,
 ) {
@@ -32,130 +53,292 @@ pub fn avoid_snake_bodies(
     let adjacent_nodes = get_adjacent_nodes(my_head);
     for adjacent_node in &adjacent_nodes {
         for snake in snakes {
-            for snake_coord in &snake.body[0..snake.body.len() - 1] {
+            let (tail, segments) = match snake.body.split_last() {
+                Some(x) => x,
+                None => continue,
+            };
+            for snake_coord in segments {
                 if adjacent_node.coord.x == snake_coord.x && adjacent_node.coord.y == snake_coord.y
                 {
-                    set.remove(&adjacent_node.movement);
+                    set.remove(
+                        &adjacent_node.movement,
+                        &format!("collides with {}", snake.name),
+                    );
                 }
             }
             // Movement into tail space is not safe if snake has just eaten
             if snake_is_stacked(snake)
-                && adjacent_node.coord.x == snake.body.last().unwrap().x
-                && adjacent_node.coord.y == snake.body.last().unwrap().y
+                && adjacent_node.coord.x == tail.x
+                && adjacent_node.coord.y == tail.y
             {
-                set.remove(&adjacent_node.movement);
+                set.remove(
+                    &adjacent_node.movement,
+                    &format!("tail of {} stays put after eating", snake.name),
+                );
             }
         }
     }
 }
 
-pub fn avoid_hazards(hazards: &Vec<Coord>, you: &Battlesnake, set: &mut WeightedMovementSet) {
-    if hazards.len() == 0 {
+// Each move costs whatever the hazards on that square do to us, and moves whose damage we can't
+// survive are off the table.
+pub fn avoid_hazards(hazards: &HazardLayer, you: &Battlesnake, set: &mut WeightedMovementSet) {
+    if hazards.is_empty() {
         return;
     }
 
     info!("Avoiding hazards");
-    let my_head = &you.head;
-    let adjacent_nodes = get_adjacent_nodes(my_head);
-    for adjacent_node in adjacent_nodes {
-        for hazard in hazards {
-            if hazard.x == adjacent_node.coord.x && hazard.y == adjacent_node.coord.y {
-                set.update_score(&adjacent_node.movement, -70);
-            }
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        let damage = hazards.health_cost(&adjacent_node.coord) - 1;
+        if damage == 0 {
+            continue;
+        }
+        if hazards
+            .health_after(you.health, &[adjacent_node.coord])
+            .is_none()
+        {
+            set.remove(
+                &adjacent_node.movement,
+                &format!("{} hazard damage with {} health", damage, you.health),
+            );
+        } else {
+            set.update_score(
+                &adjacent_node.movement,
+                -5 * damage as isize,
+                &format!("{} hazard damage", damage),
+            );
         }
     }
 }
 
 pub fn handle_opponent_heads(
-    snakes: &Vec<Battlesnake>,
+    board: &Board,
     you: &Battlesnake,
+    walls: &HashSet<Coord>,
     set: &mut WeightedMovementSet,
 ) {
     info!("Avoiding opponent snake heads if short");
     let my_head = &you.head;
-    let opponents: Vec<_> = snakes.iter().filter(|x| x.id != you.id).collect();
+    let opponents: Vec<_> = board.snakes.iter().filter(|x| x.id != you.id).collect();
     let adjacent_nodes = get_adjacent_nodes(my_head);
     for adjacent_node in &adjacent_nodes {
         for opponent in &opponents {
             let adjacent_opponent_nodes = get_adjacent_nodes(&opponent.head);
             for adjacent_opponent_node in &adjacent_opponent_nodes {
+                // Nobody moves into a wall, so there's no meeting there
+                if walls.contains(&adjacent_opponent_node.coord) {
+                    continue;
+                }
                 if adjacent_node.coord.x == adjacent_opponent_node.coord.x
                     && adjacent_node.coord.y == adjacent_opponent_node.coord.y
                 {
+                    if you.length <= opponent.length {
+                        set.classify(
+                            &adjacent_node.movement,
+                            SafetyTier::Risky,
+                            &format!("{} could meet us head-to-head", opponent.name),
+                        );
+                    }
                     if you.length < opponent.length {
-                        set.update_score(&adjacent_node.movement, -60);
+                        set.update_score(
+                            &adjacent_node.movement,
+                            -60,
+                            &format!("possible head-to-head with longer {}", opponent.name),
+                        );
                     } else if you.length == opponent.length {
-                        set.update_score(&adjacent_node.movement, -50);
+                        set.update_score(
+                            &adjacent_node.movement,
+                            -50,
+                            &format!("possible head-to-head with equal {}", opponent.name),
+                        );
                     } else {
-                        set.update_score(&adjacent_node.movement, 30);
+                        set.update_score(
+                            &adjacent_node.movement,
+                            30,
+                            &format!("possible head-to-head with shorter {}", opponent.name),
+                        );
                     }
                 }
             }
         }
     }
+
+    handle_threat_zones(board, you, walls, set);
 }
 
-pub fn scan_food(board: &Board, you: &Battlesnake, set: &mut WeightedMovementSet) {
-    if board.food.len() == 0 {
+// Looks a turn further ahead than `handle_opponent_heads`: after each move, how likely is it that
+// an opponent can be on every square we could escape to next. Entering a corridor where a longer
+// snake can meet us whichever way we turn is nearly as bad as meeting it now.
+fn handle_threat_zones(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    set: &mut WeightedMovementSet,
+) {
+    let threats = threats(board, you, walls);
+    if threats.is_empty() {
         return;
     }
-    info!("Searching for food");
+    let blocked = blocked_squares(board, walls);
 
-    let my_head = you.head.to_owned();
-    let snake_coords = get_all_snake_coords(&board.snakes);
-
-    let mut food_movements = FifoQueue::<Movement>::new();
-    let mut frontier = FifoQueue::<LeafNode>::new();
-    let mut visited_coords: HashSet<_> = vec![my_head].into_iter().collect();
-
-    let adjacent_nodes = get_adjacent_nodes(&my_head);
-    for adjacent_node in adjacent_nodes {
-        if set.moves.contains(&adjacent_node.movement) {
-            frontier.enqueue(LeafNode {
-                node: adjacent_node,
-                parent: adjacent_node,
-            });
-            visited_coords.insert(adjacent_node.coord);
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        let movement = &adjacent_node.movement;
+        if !set.moves.contains(movement) {
+            continue;
+        }
+        let escapes: Vec<Coord> = get_adjacent_nodes(&adjacent_node.coord)
+            .into_iter()
+            .map(|x| x.coord)
+            .filter(|x| board.contains(x) && !blocked.contains(x) && x != &you.head)
+            .collect();
+        if escapes.is_empty() {
+            continue;
+        }
+
+        for threat in &threats {
+            let probabilities = escapes.iter().map(|x| threat.probability(x));
+            if you.length <= threat.length {
+                // We take whichever escape is least likely to be covered
+                let risk = probabilities.fold(1.0, f64::min);
+                if risk == 0.0 {
+                    continue;
+                }
+                let (penalty, comparison) = if you.length < threat.length {
+                    (60.0, "longer")
+                } else {
+                    (50.0, "equal")
+                };
+                let reason = format!(
+                    "{} {} can cover every escape next turn, {:.0}% chance",
+                    comparison,
+                    threat.name,
+                    risk * 100.0
+                );
+                if risk >= 0.5 {
+                    set.classify(movement, SafetyTier::Risky, &reason);
+                }
+                set.update_score(movement, -(penalty * risk).round() as isize, &reason);
+            } else {
+                let chance = probabilities.fold(0.0, f64::max);
+                if chance == 0.0 {
+                    continue;
+                }
+                set.update_score(
+                    movement,
+                    (15.0 * chance).round() as isize,
+                    &format!(
+                        "could meet shorter {} head-to-head next turn, {:.0}% chance",
+                        threat.name,
+                        chance * 100.0
+                    ),
+                );
+            }
         }
     }
+}
 
-    loop {
-        let current = match frontier.dequeue() {
-            Some(x) => x,
-            None => break,
-        };
-        let coord = &current.node.coord;
+pub fn scan_food(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+    set: &mut WeightedMovementSet,
+) {
+    if board.food.is_empty() {
+        return;
+    }
+    info!("Searching for food");
+
+    let costs = food_costs(board, you, walls, hazards, |x| set.moves.contains(x));
+    let hunger = hunger(board, you, &costs);
+    info!("Health {}, {:?}", you.health, hunger);
+    if hunger == Hunger::Full {
+        return;
+    }
 
+    let my_head = you.head.to_owned();
+    let mut snake_coords = get_all_snake_coords(&board.snakes);
+    snake_coords.extend(walls);
+
+    let cost_model = CostModel::new(board, you, walls, hazards);
+
+    // Food is ranked by the cost of getting to it rather than by distance, so food behind hazard
+    // ranks below food that's slightly further away but safe to reach.
+    let map = distance_map(
+        board.width,
+        board.height,
+        &sources_from_moves(
+            &my_head,
+            |x| set.moves.contains(x),
+            |from, to| cost_model.edge_cost(from, to),
+        ),
+        |x, _| x != &my_head && !snake_coords.contains(x),
+        |from, to| cost_model.edge_cost(from, to),
+        |_, _| false,
+    );
+
+    // Food an opponent gets to first isn't worth chasing, and walking into their head on the food
+    // square is worse than not eating.
+    let contest = FoodContest::new(board, you, walls);
+    let mut food_movements = FifoQueue::<(Movement, Coord, Contest)>::new();
+    for coord in map.reached() {
         if board.food.contains(coord) {
-            info!("Found food at {} {}", coord.x, coord.y);
-            food_movements.enqueue(current.parent.movement);
-        }
+            let path = map.path_to(coord);
+            let steps = path.len();
+            let result = contest.contest(coord, steps);
+            info!(
+                "Found food at {} {}, path cost {}, {} moves away, nearest opponent {:?} moves away: {:?}",
+                coord.x,
+                coord.y,
+                map.distance(coord).unwrap_or_default(),
+                steps,
+                contest.nearest_opponent(coord),
+                result
+            );
+            if result == Contest::Losing {
+                continue;
+            }
+            if hazards.health_after(you.health, &path).is_none() {
+                info!(
+                    "Hazards on the way to ({}, {}) would kill us",
+                    coord.x, coord.y
+                );
+                continue;
+            }
 
-        let adjacent_nodes = get_adjacent_nodes(coord);
-        for adjacent_node in adjacent_nodes {
-            if adjacent_node.coord.x >= 0
-                && adjacent_node.coord.x < (board.width as i32)
-                && adjacent_node.coord.y >= 0
-                && adjacent_node.coord.y < (board.height as i32)
-                && !snake_coords.contains(&adjacent_node.coord)
-                && !visited_coords.contains(&adjacent_node.coord)
-            {
-                frontier.enqueue(LeafNode {
-                    node: adjacent_node,
-                    parent: current.parent,
-                });
-                visited_coords.insert(adjacent_node.coord);
+            // Our tail stays put the turn we eat, so check there's still room once we've grown
+            let required_space = you.length as usize + 1;
+            let available_space = space_after_eating(board, you, walls, &path, required_space);
+            if available_space < required_space {
+                let reason = format!(
+                    "eating at ({}, {}) leaves only {} of {} required spaces",
+                    coord.x, coord.y, available_space, required_space
+                );
+                info!("{}", reason);
+                if let (1, Some(movement)) = (steps, map.first_move(coord)) {
+                    set.update_score(&movement, -40, &reason);
+                }
+                continue;
+            }
+
+            if let Some(movement) = map.first_move(coord) {
+                food_movements.enqueue((movement, *coord, result));
             }
         }
     }
 
     let mut probability = 20;
-    loop {
-        let movement = match food_movements.dequeue() {
-            Some(x) => x,
-            None => break,
+    while let Some((movement, food, result)) = food_movements.dequeue() {
+        let (amount, reason) = match result {
+            Contest::Uncontested => (probability + 10, "toward uncontested food"),
+            _ => (probability, "toward food"),
         };
-        set.update_score(&movement, probability);
+        let amount = amount * hunger.appetite();
+        set.update_score(
+            &movement,
+            amount,
+            &format!("{} at ({}, {})", reason, food.x, food.y),
+        );
         probability -= 10;
         if probability == 0 {
             break;
@@ -163,140 +346,286 @@ pub fn scan_food(board: &Board, you: &Battlesnake, set: &mut WeightedMovementSet
     }
 }
 
-pub fn avoid_small_spaces(board: &Board, you: &Battlesnake, set: &mut WeightedMovementSet) {
+// When the nearest food is barely within reach, getting there matters more than anything short
+// of dying this turn, and any move that can't reach food in time is as good as dead.
+pub fn avoid_starvation(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+    set: &mut WeightedMovementSet,
+) {
+    let costs = food_costs(board, you, walls, hazards, |x| set.moves.contains(x));
+    if hunger(board, you, &costs) != Hunger::Starving {
+        return;
+    }
+    info!("Starving with {} health", you.health);
+
+    for (movement, cost) in &costs {
+        match cost {
+            Some(x) if *x <= you.health => {}
+            _ => {
+                let reason = format!("can't reach food with {} health", you.health);
+                set.classify(movement, SafetyTier::LikelyDeath, &reason);
+                set.update_score(movement, -50, &reason);
+            }
+        }
+    }
+
+    let nearest = costs
+        .iter()
+        .filter_map(|(movement, cost)| cost.map(|x| (x, *movement)))
+        .min_by_key(|x| x.0);
+    if let Some((cost, movement)) = nearest {
+        if cost <= you.health {
+            set.update_score(
+                &movement,
+                60,
+                &format!("emergency feeding, food {} health away", cost),
+            );
+        }
+    }
+}
+
+pub fn avoid_small_spaces(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    set: &mut WeightedMovementSet,
+) {
     info!("Check if snake can fit in space");
 
     let my_head = you.head.to_owned();
-    let mut required_space = you.length as usize;
-    let snake_coords = get_all_snake_coords(&board.snakes);
-
-    let mut frontier = FifoQueue::<LeafNode>::new();
+    let required_space = you.length as usize;
+    let mut vacate_times = VacateTimes::new(board);
+    vacate_times.add_walls(walls);
+    // Eating keeps our tail where it is for an extra turn
+    let mut fed_times = VacateTimes::new(board);
+    fed_times.add_walls(walls);
+    fed_times.delay_snake(you, 1);
 
-    let adjacent_nodes = get_adjacent_nodes(&my_head);
-    for adjacent_node in adjacent_nodes {
+    for adjacent_node in get_adjacent_nodes(&my_head) {
         let movement = &adjacent_node.movement;
         if !set.moves.contains(movement) {
             continue;
         }
 
-        frontier.enqueue(LeafNode {
-            node: adjacent_node,
-            parent: adjacent_node,
-        });
-        let mut visited_coords: HashSet<_> =
-            vec![my_head, adjacent_node.coord].into_iter().collect();
-
-        loop {
-            if visited_coords.len() >= required_space {
-                frontier.clear();
-                break;
-            }
-            let current = match frontier.dequeue() {
-                Some(x) => x,
-                None => {
-                    info!(
-                        "movement: {:?}, required space: {}, available space: {}",
-                        movement,
-                        required_space,
-                        visited_coords.len()
-                    );
-                    if required_space > visited_coords.len() {
-                        set.update_score(movement, -70);
-                    }
-                    break;
-                }
-            };
-            let coord = &current.node.coord;
-
-            let adjacent_nodes = get_adjacent_nodes(coord);
-            for adjacent_node in adjacent_nodes {
-                if adjacent_node.coord.x >= 0
-                    && adjacent_node.coord.x < (board.width as i32)
-                    && adjacent_node.coord.y >= 0
-                    && adjacent_node.coord.y < (board.height as i32)
-                    && !visited_coords.contains(&adjacent_node.coord)
-                {
-                    if !snake_coords.contains(&adjacent_node.coord) {
-                        frontier.enqueue(LeafNode {
-                            node: adjacent_node,
-                            parent: current.parent,
-                        });
-                        visited_coords.insert(adjacent_node.coord);
-                    } else if &adjacent_node.coord != &my_head
-                        && you.body.contains(&adjacent_node.coord)
-                    {
-                        required_space -= 1;
-                    }
-                }
-            }
+        let times = if board.food.contains(&adjacent_node.coord) {
+            &fed_times
+        } else {
+            &vacate_times
+        };
+        let available_space = reachable_area(board, times, &adjacent_node.coord, required_space);
+
+        info!(
+            "movement: {:?}, required space: {}, available space: {}",
+            movement, required_space, available_space
+        );
+        if required_space > available_space {
+            set.classify(movement, SafetyTier::LikelyDeath, "dead end");
+            set.update_score(
+                movement,
+                -70,
+                &format!(
+                    "only {} of {} required spaces reachable",
+                    available_space, required_space
+                ),
+            );
         }
     }
 }
 
-pub fn scan_tail(board: &Board, you: &Battlesnake, set: &mut WeightedMovementSet) {
-    info!("Searching for tail");
+// Looks for moves into a region that is only open through a single square, where the region is
+// too small for us and an opponent could get to that square and seal us in before we're out.
+pub fn avoid_chokepoints(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    set: &mut WeightedMovementSet,
+) {
+    info!("Checking for chokepoints");
 
-    let my_head = you.head.to_owned();
-    let my_tail = you.body.last().unwrap();
-    let mut snake_coords = get_all_snake_coords(&board.snakes);
-    snake_coords.remove(my_tail);
+    let required_space = you.length as usize;
 
-    let mut tail_movement: Option<Movement> = None;
-    let mut frontier = FifoQueue::<LeafNode>::new();
-    let mut visited_coords: HashSet<_> = vec![my_head].into_iter().collect();
-
-    let adjacent_nodes = get_adjacent_nodes(&my_head);
-    for adjacent_node in adjacent_nodes {
-        if set.moves.contains(&adjacent_node.movement) {
-            frontier.enqueue(LeafNode {
-                node: adjacent_node,
-                parent: adjacent_node,
-            });
-            visited_coords.insert(adjacent_node.coord);
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        let movement = &adjacent_node.movement;
+        if !set.moves.contains(movement) {
+            continue;
         }
-    }
 
-    loop {
-        let current = match frontier.dequeue() {
-            Some(x) => x,
-            None => break,
+        let analysis = analyze_move(board, you, walls, &adjacent_node.coord);
+        info!(
+            "movement: {:?}, area: {}, chokepoints: {}, regions: {}",
+            movement,
+            analysis.area,
+            analysis.articulation_points.len(),
+            analysis.regions.len()
+        );
+        let doorway = match analysis.doorway {
+            Some(x) if x.region_size < required_space => x,
+            _ => continue,
         };
-        let coord = &current.node.coord;
 
-        if coord == my_tail {
-            info!("Found tail at {} {}", coord.x, coord.y);
-            tail_movement = Some(current.parent.movement);
-            break;
+        // We'd have to walk back out through the gap to escape, so any opponent that can get
+        // there as soon as we can is able to close it.
+        let our_distance = manhattan_distance(&adjacent_node.coord, &doorway.gap) + 1;
+        let contested = board
+            .snakes
+            .iter()
+            .any(|x| x.id != you.id && manhattan_distance(&x.head, &doorway.gap) <= our_distance);
+        if contested {
+            let reason = format!(
+                "region of {} squares behind 1-wide gap at ({}, {})",
+                doorway.region_size, doorway.gap.x, doorway.gap.y
+            );
+            set.classify(movement, SafetyTier::Risky, &reason);
+            set.update_score(movement, -40, &reason);
         }
+    }
+}
 
-        let adjacent_nodes = get_adjacent_nodes(coord);
-        for adjacent_node in adjacent_nodes {
-            if adjacent_node.coord.x >= 0
-                && adjacent_node.coord.x < (board.width as i32)
-                && adjacent_node.coord.y >= 0
-                && adjacent_node.coord.y < (board.height as i32)
-                && !snake_coords.contains(&adjacent_node.coord)
-                && !visited_coords.contains(&adjacent_node.coord)
-            {
-                frontier.enqueue(LeafNode {
-                    node: adjacent_node,
-                    parent: current.parent,
-                });
-                visited_coords.insert(adjacent_node.coord);
+// Looks for moves that shut an opponent into less space than its length, e.g. by following a
+// wall or body line to close off the gap it's heading for, while leaving us enough room.
+pub fn trap_opponents(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    set: &mut WeightedMovementSet,
+) {
+    let opponents: Vec<&Battlesnake> = board.snakes.iter().filter(|x| x.id != you.id).collect();
+    if opponents.is_empty() {
+        return;
+    }
+    info!("Looking for opponents to trap");
+
+    let mut vacate_times = VacateTimes::new(board);
+    vacate_times.add_walls(walls);
+    let required_space = you.length as usize;
+
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        let movement = &adjacent_node.movement;
+        if !set.moves.contains(movement) {
+            continue;
+        }
+        if reachable_area(board, &vacate_times, &adjacent_node.coord, required_space)
+            < required_space
+        {
+            continue;
+        }
+
+        // Our new head stays put until our whole body has moved past it
+        let mut times = VacateTimes::new(board);
+        times.add_walls(walls);
+        times.occupy(&adjacent_node.coord, you.body.len() + 1);
+
+        for opponent in &opponents {
+            let length = opponent.length as usize;
+            let before = area_from_head(board, &vacate_times, opponent, length);
+            let after = area_from_head(board, &times, opponent, length);
+            if before < length || after >= length {
+                continue;
             }
+            info!(
+                "movement: {:?} traps {} in {} squares",
+                movement, opponent.name, after
+            );
+            set.update_score(
+                movement,
+                30 + 2 * (length - after) as isize,
+                &format!("traps {} in {} squares", opponent.name, after),
+            );
         }
     }
+}
+
+// Without opponents the only way to lose is to trap ourselves or starve, so stick to a route that
+// can't trap us however long we get.
+pub fn follow_cycle(
+    board: &Board,
+    you: &Battlesnake,
+    cycle: Option<&HamiltonianCycle>,
+    set: &mut WeightedMovementSet,
+) {
+    info!("Following the cycle");
+    let cycle = match cycle {
+        Some(x) => x,
+        None => return,
+    };
+    if let Some((movement, reason)) = solo_move(board, you, cycle) {
+        set.update_score(&movement, 200, &reason);
+    }
+}
 
-    match tail_movement {
-        Some(x) => {
-            set.update_score(&x, 20);
+// Once nobody else can get into our space, food and tails stop mattering and the only goal is
+// to last as long as possible, so follow the plan for filling the region. `next` is the square
+// the plan goes to next and how many turns it lasts, from `survival`.
+pub fn fill_space(you: &Battlesnake, next: Option<(Coord, usize)>, set: &mut WeightedMovementSet) {
+    info!("Checking if isolated");
+
+    let (square, planned) = match next {
+        Some(x) => x,
+        None => return,
+    };
+    for adjacent_node in get_adjacent_nodes(&you.head) {
+        if adjacent_node.coord == square {
+            set.update_score(
+                &adjacent_node.movement,
+                100,
+                &format!("fills isolated region, {} turns planned", planned),
+            );
         }
-        None => {}
+    }
+}
+
+pub fn scan_tail(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+    set: &mut WeightedMovementSet,
+) {
+    info!("Searching for tail");
+
+    let my_head = you.head.to_owned();
+    let my_tail = match you.body.last() {
+        Some(x) => x,
+        None => return,
     };
+    let mut snake_coords = get_all_snake_coords(&board.snakes);
+    snake_coords.remove(my_tail);
+    snake_coords.extend(walls);
+
+    let cost_model = CostModel::new(board, you, walls, hazards);
+
+    let path = find_path(
+        board.width,
+        board.height,
+        &sources_from_moves(
+            &my_head,
+            |x| set.moves.contains(x),
+            |from, to| cost_model.edge_cost(from, to),
+        ),
+        my_tail,
+        |x, _| x != &my_head && !snake_coords.contains(x),
+        |from, to| cost_model.edge_cost(from, to),
+    );
+
+    if let Some(path) = path {
+        info!(
+            "Found tail at {} {}, {} moves away with path cost {}",
+            my_tail.x,
+            my_tail.y,
+            path.coords.len(),
+            path.cost
+        );
+        if let Some(x) = path.first_move {
+            set.update_score(&x, 20, "toward own tail");
+        }
+    }
 }
 
 pub fn snake_is_stacked(snake: &Battlesnake) -> bool {
-    for i in 0..snake.body.len() - 1 {
+    for i in 0..snake.body.len() {
         for j in i + 1..snake.body.len() {
             if snake.body[i] == snake.body[j] {
                 return true;
@@ -306,7 +635,7 @@ pub fn snake_is_stacked(snake: &Battlesnake) -> bool {
     false
 }
 
-pub fn get_all_snake_coords(snakes: &Vec<Battlesnake>) -> HashSet<Coord> {
+pub fn get_all_snake_coords(snakes: &[Battlesnake]) -> HashSet<Coord> {
     let mut coords: HashSet<Coord> = HashSet::new();
     for snake in snakes {
         for coord in &snake.body {
@@ -7,17 +7,40 @@ use rocket::http::Status;
 use rocket::serde::json::Json;
 use serde_json::{json, Value};
 use std::env;
+use std::time::Instant;
 
+use crate::logging::LogContext;
 use crate::request::GameState;
 
 mod auth;
+mod chokepoints;
+mod cost_model;
+mod explain;
 mod fifo_queue;
+mod food;
 mod graph;
+mod hazard;
+mod heuristic;
+mod hunger;
+mod logging;
 mod logic;
+mod map;
 mod movement_set;
+mod parallel;
+mod pipeline;
 mod request;
 mod response;
+mod safety;
+mod search;
+mod selection;
+mod simulation;
 mod snakes;
+mod solo;
+mod space;
+mod survival;
+mod threat;
+mod transposition;
+mod zobrist;
 
 #[get("/")]
 fn handle_index_rusty(_key: auth::ApiKey<'_>) -> Json<Value> {
@@ -26,31 +49,49 @@ fn handle_index_rusty(_key: auth::ApiKey<'_>) -> Json<Value> {
 
 #[post("/start", format = "json", data = "<start_req>")]
 fn handle_start_rusty(start_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::rusty::start(
-        &start_req.game,
-        &start_req.turn,
-        &start_req.board,
-        &start_req.you,
-    );
+    logging::with_context(LogContext::new("rusty", &start_req), || {
+        snakes::rusty::start(
+            &start_req.game,
+            &start_req.turn,
+            &start_req.board,
+            &start_req.you,
+        )
+    });
 
     Status::Ok
 }
 
 #[post("/move", format = "json", data = "<move_req>")]
-fn handle_move_rusty(move_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Json<Value> {
-    let response = snakes::rusty::get_move(
-        &move_req.game,
-        &move_req.turn,
-        &move_req.board,
-        &move_req.you,
-    );
+async fn handle_move_rusty(
+    move_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let move_req = move_req.into_inner();
+    let context = LogContext::new("rusty", &move_req);
+    safety::guard_move_blocking(move_req, context, |state| {
+        snakes::rusty::get_move(&state.game, &state.turn, &state.board, &state.you)
+    })
+    .await
+}
 
-    Json(json!(response))
+#[post("/explain", format = "json", data = "<explain_req>")]
+async fn handle_explain_rusty(
+    explain_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let explain_req = explain_req.into_inner();
+    let context = LogContext::new("rusty", &explain_req);
+    safety::guard_explain_blocking(explain_req, context, |state| {
+        snakes::rusty::explain(&state.game, &state.turn, &state.board, &state.you)
+    })
+    .await
 }
 
 #[post("/end", format = "json", data = "<end_req>")]
 fn handle_end_rusty(end_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::rusty::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you);
+    logging::with_context(LogContext::new("rusty", &end_req), || {
+        snakes::rusty::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you)
+    });
 
     Status::Ok
 }
@@ -62,31 +103,78 @@ fn handle_index_righty(_key: auth::ApiKey<'_>) -> Json<Value> {
 
 #[post("/start", format = "json", data = "<start_req>")]
 fn handle_start_righty(start_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::righty::start(
-        &start_req.game,
-        &start_req.turn,
-        &start_req.board,
-        &start_req.you,
-    );
+    logging::with_context(LogContext::new("righty", &start_req), || {
+        snakes::righty::start(
+            &start_req.game,
+            &start_req.turn,
+            &start_req.board,
+            &start_req.you,
+        )
+    });
 
     Status::Ok
 }
 
 #[post("/move", format = "json", data = "<move_req>")]
-fn handle_move_righty(move_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Json<Value> {
-    let response = snakes::righty::get_move(
-        &move_req.game,
-        &move_req.turn,
-        &move_req.board,
-        &move_req.you,
-    );
-
-    Json(json!(response))
+async fn handle_move_righty(
+    move_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let move_req = move_req.into_inner();
+    let context = LogContext::new("righty", &move_req);
+    safety::guard_move_blocking(move_req, context, |state| {
+        snakes::righty::get_move(&state.game, &state.turn, &state.board, &state.you)
+    })
+    .await
 }
 
 #[post("/end", format = "json", data = "<end_req>")]
 fn handle_end_righty(end_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::righty::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you);
+    logging::with_context(LogContext::new("righty", &end_req), || {
+        snakes::righty::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you)
+    });
+
+    Status::Ok
+}
+
+#[get("/")]
+fn handle_index_brainy(_key: auth::ApiKey<'_>) -> Json<Value> {
+    Json(json!(snakes::brainy::info()))
+}
+
+#[post("/start", format = "json", data = "<start_req>")]
+fn handle_start_brainy(start_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
+    logging::with_context(LogContext::new("brainy", &start_req), || {
+        snakes::brainy::start(
+            &start_req.game,
+            &start_req.turn,
+            &start_req.board,
+            &start_req.you,
+        )
+    });
+
+    Status::Ok
+}
+
+#[post("/move", format = "json", data = "<move_req>")]
+async fn handle_move_brainy(
+    move_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let received = Instant::now();
+    let move_req = move_req.into_inner();
+    let context = LogContext::new("brainy", &move_req);
+    safety::guard_move_blocking(move_req, context, move |state| {
+        snakes::brainy::get_move(&state.game, &state.turn, &state.board, &state.you, received)
+    })
+    .await
+}
+
+#[post("/end", format = "json", data = "<end_req>")]
+fn handle_end_brainy(end_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
+    logging::with_context(LogContext::new("brainy", &end_req), || {
+        snakes::brainy::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you)
+    });
 
     Status::Ok
 }
@@ -98,31 +186,49 @@ fn handle_index_dizzy(_key: auth::ApiKey<'_>) -> Json<Value> {
 
 #[post("/start", format = "json", data = "<start_req>")]
 fn handle_start_dizzy(start_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::dizzy::start(
-        &start_req.game,
-        &start_req.turn,
-        &start_req.board,
-        &start_req.you,
-    );
+    logging::with_context(LogContext::new("dizzy", &start_req), || {
+        snakes::dizzy::start(
+            &start_req.game,
+            &start_req.turn,
+            &start_req.board,
+            &start_req.you,
+        )
+    });
 
     Status::Ok
 }
 
 #[post("/move", format = "json", data = "<move_req>")]
-fn handle_move_dizzy(move_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Json<Value> {
-    let response = snakes::dizzy::get_move(
-        &move_req.game,
-        &move_req.turn,
-        &move_req.board,
-        &move_req.you,
-    );
+async fn handle_move_dizzy(
+    move_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let move_req = move_req.into_inner();
+    let context = LogContext::new("dizzy", &move_req);
+    safety::guard_move_blocking(move_req, context, |state| {
+        snakes::dizzy::get_move(&state.game, &state.turn, &state.board, &state.you)
+    })
+    .await
+}
 
-    Json(json!(response))
+#[post("/explain", format = "json", data = "<explain_req>")]
+async fn handle_explain_dizzy(
+    explain_req: Json<GameState>,
+    _key: auth::ApiKey<'_>,
+) -> safety::MoveResult {
+    let explain_req = explain_req.into_inner();
+    let context = LogContext::new("dizzy", &explain_req);
+    safety::guard_explain_blocking(explain_req, context, |state| {
+        snakes::dizzy::explain(&state.game, &state.turn, &state.board, &state.you)
+    })
+    .await
 }
 
 #[post("/end", format = "json", data = "<end_req>")]
 fn handle_end_dizzy(end_req: Json<GameState>, _key: auth::ApiKey<'_>) -> Status {
-    snakes::dizzy::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you);
+    logging::with_context(LogContext::new("dizzy", &end_req), || {
+        snakes::dizzy::end(&end_req.game, &end_req.turn, &end_req.board, &end_req.you)
+    });
 
     Status::Ok
 }
@@ -142,10 +248,7 @@ fn rocket() -> _ {
         env::set_var("RUST_LOG", "info");
     }
 
-    match env_logger::try_init() {
-        Ok(_) => (),
-        Err(_) => (),
-    }
+    logging::init();
 
     info!("Starting Battlesnake Server...");
 
@@ -161,6 +264,7 @@ fn rocket() -> _ {
                 handle_index_rusty,
                 handle_start_rusty,
                 handle_move_rusty,
+                handle_explain_rusty,
                 handle_end_rusty
             ],
         )
@@ -170,6 +274,7 @@ fn rocket() -> _ {
                 handle_index_rusty,
                 handle_start_rusty,
                 handle_move_rusty,
+                handle_explain_rusty,
                 handle_end_rusty
             ],
         )
@@ -188,7 +293,17 @@ fn rocket() -> _ {
                 handle_index_dizzy,
                 handle_start_dizzy,
                 handle_move_dizzy,
+                handle_explain_dizzy,
                 handle_end_dizzy
             ],
         )
+        .mount(
+            "/brainy",
+            routes![
+                handle_index_brainy,
+                handle_start_brainy,
+                handle_move_brainy,
+                handle_end_brainy
+            ],
+        )
 }
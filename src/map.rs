@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+
+use crate::request::{Board, Coord, Game};
+
+// Maps whose hazards are laid out as the walls of a maze rather than areas to cut across.
+const WALL_MAPS: [&str; 1] = ["arcade_maze"];
+// A hazard doing this much damage kills anything that enters it, so it may as well be a wall.
+const WALL_DAMAGE: u32 = 100;
+
+pub fn hazards_are_walls(game: &Game) -> bool {
+    game.map.as_deref().is_some_and(|x| WALL_MAPS.contains(&x))
+        || game.ruleset.settings.hazard_damage_per_turn >= WALL_DAMAGE
+}
+
+// Squares that can never be entered, on top of the edges of the board.
+pub fn walls(game: &Game, board: &Board) -> HashSet<Coord> {
+    if !hazards_are_walls(game) {
+        return HashSet::new();
+    }
+    board.hazards.iter().copied().collect()
+}
+
+#[test]
+fn maze_hazards_are_walls() {
+    let game: Game = serde_json::from_str(
+        r#"{"id": "unique-game-id", "ruleset": {"name": "wrapped"}, "map": "arcade_maze", "timeout": 500}"#,
+    )
+    .expect("failed to parse game");
+    assert!(hazards_are_walls(&game));
+
+    let game: Game = serde_json::from_str(
+        r#"{
+          "id": "unique-game-id",
+          "ruleset": {"name": "royale", "settings": {"hazardDamagePerTurn": 14}},
+          "map": "royale",
+          "timeout": 500
+        }"#,
+    )
+    .expect("failed to parse game");
+    assert!(!hazards_are_walls(&game));
+}
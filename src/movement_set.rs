@@ -1,10 +1,13 @@
 use std::borrow::Borrow;
+use std::cmp::Reverse;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 
-use log::info;
+use log::debug;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum Movement {
     Right,
     Left,
@@ -13,6 +16,15 @@ pub enum Movement {
 }
 
 impl Movement {
+    pub fn all() -> [Movement; 4] {
+        [
+            Movement::Up,
+            Movement::Down,
+            Movement::Left,
+            Movement::Right,
+        ]
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Movement::Up => "up",
@@ -23,10 +35,23 @@ impl Movement {
     }
 }
 
+// How dangerous a move is, from worst to best. Moves are compared by tier first, and the score
+// only decides between moves in the same tier, so no amount of bonuses can make a dead end look
+// better than a move that isn't one.
+#[derive(Deserialize, Serialize, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyTier {
+    CertainDeath,
+    LikelyDeath,
+    Risky,
+    Safe,
+}
+
 #[derive(Debug, Eq)]
 pub struct WeightedMovement {
     pub movement: Movement,
     pub success_score: isize,
+    pub tier: SafetyTier,
 }
 
 impl PartialEq for WeightedMovement {
@@ -47,8 +72,45 @@ impl Borrow<Movement> for WeightedMovement {
     }
 }
 
+// One change made to a move while scoring, in the order it happened. `source` is the heuristic
+// that was running when the change was made.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LedgerEntry {
+    Adjusted {
+        movement: Movement,
+        source: String,
+        delta: isize,
+        reason: String,
+    },
+    Removed {
+        movement: Movement,
+        source: String,
+        reason: String,
+    },
+    Classified {
+        movement: Movement,
+        source: String,
+        tier: SafetyTier,
+        reason: String,
+    },
+}
+
+impl LedgerEntry {
+    pub fn movement(&self) -> Movement {
+        match self {
+            LedgerEntry::Adjusted { movement, .. } => *movement,
+            LedgerEntry::Removed { movement, .. } => *movement,
+            LedgerEntry::Classified { movement, .. } => *movement,
+        }
+    }
+}
+
 pub struct WeightedMovementSet {
     pub moves: HashSet<WeightedMovement>,
+    ledger: Vec<LedgerEntry>,
+    source: String,
+    weight: f64,
 }
 
 impl WeightedMovementSet {
@@ -58,42 +120,96 @@ impl WeightedMovementSet {
                 WeightedMovement {
                     movement: Movement::Up,
                     success_score: 100,
+                    tier: SafetyTier::Safe,
                 },
                 WeightedMovement {
                     movement: Movement::Down,
                     success_score: 100,
+                    tier: SafetyTier::Safe,
                 },
                 WeightedMovement {
                     movement: Movement::Left,
                     success_score: 100,
+                    tier: SafetyTier::Safe,
                 },
                 WeightedMovement {
                     movement: Movement::Right,
                     success_score: 100,
+                    tier: SafetyTier::Safe,
                 },
             ]
             .into_iter()
             .collect(),
+            ledger: vec![],
+            source: "unattributed".to_string(),
+            weight: 1.0,
         }
     }
 
-    pub fn remove(&mut self, movement: &Movement) {
-        self.moves.remove(movement);
-        info!("Set {} as unsafe", movement.as_str());
+    // Attributes every change made by `f` to `source` in the ledger, and scales every
+    // `update_score` made by `f` by `weight`.
+    pub fn with_source<F>(&mut self, source: &str, weight: f64, f: F)
+    where
+        F: FnOnce(&mut WeightedMovementSet),
+    {
+        let previous_source = std::mem::replace(&mut self.source, source.to_string());
+        let previous_weight = std::mem::replace(&mut self.weight, weight);
+        f(self);
+        self.source = previous_source;
+        self.weight = previous_weight;
     }
 
-    pub fn set_score(&mut self, movement: &Movement, new_score: isize) {
-        match self.moves.get(movement) {
-            Some(_) => {
-                self.moves.replace(WeightedMovement {
+    pub fn ledger(&self) -> &[LedgerEntry] {
+        &self.ledger
+    }
+
+    pub fn history(&self, movement: &Movement) -> Vec<&LedgerEntry> {
+        self.ledger
+            .iter()
+            .filter(|x| &x.movement() == movement)
+            .collect()
+    }
+
+    pub fn remove(&mut self, movement: &Movement, reason: &str) {
+        if self.moves.remove(movement) {
+            debug!(
+                "{}: set {} as unsafe, {}",
+                self.source,
+                movement.as_str(),
+                reason
+            );
+            self.ledger.push(LedgerEntry::Removed {
+                movement: movement.to_owned(),
+                source: self.source.to_owned(),
+                reason: reason.to_string(),
+            });
+        }
+    }
+
+    pub fn set_score(&mut self, movement: &Movement, new_score: isize, reason: &str) {
+        match self.moves.take(movement) {
+            Some(mut x) => {
+                let old_score = x.success_score;
+                x.success_score = new_score;
+                self.moves.insert(x);
+                debug!(
+                    "{}: set {} as probability of {}, {}",
+                    self.source,
+                    movement.as_str(),
+                    new_score,
+                    reason
+                );
+                self.ledger.push(LedgerEntry::Adjusted {
                     movement: movement.to_owned(),
-                    success_score: new_score,
+                    source: self.source.to_owned(),
+                    delta: new_score - old_score,
+                    reason: reason.to_string(),
                 });
-                info!("Set {} as probability of {}", movement.as_str(), new_score);
             }
             None => {
-                info!(
-                    "Tried to set {} to have a probability of {}, but {} is not a safe move",
+                debug!(
+                    "{}: tried to set {} to have a probability of {}, but {} is not a safe move",
+                    self.source,
                     movement.as_str(),
                     new_score,
                     movement.as_str()
@@ -102,15 +218,16 @@ impl WeightedMovementSet {
         }
     }
 
-    pub fn update_score(&mut self, movement: &Movement, amount: isize) {
-        match self.moves.get(movement) {
+    pub fn update_score(&mut self, movement: &Movement, amount: isize, reason: &str) {
+        match self.score(movement) {
             Some(x) => {
-                let new_probability = x.success_score + amount;
-                self.set_score(movement, new_probability);
+                let amount = (amount as f64 * self.weight).round() as isize;
+                self.set_score(movement, x + amount, reason);
             }
             None => {
-                info!(
-                    "Tried to increment/decrement the probability of {} by {}, but {} is not a safe move",
+                debug!(
+                    "{}: tried to increment/decrement the probability of {} by {}, but {} is not a safe move",
+                    self.source,
                     movement.as_str(),
                     amount,
                     movement.as_str()
@@ -119,24 +236,106 @@ impl WeightedMovementSet {
         }
     }
 
+    // Lowers a move to `tier` if it isn't already at or below it. Use `remove` for certain death.
+    pub fn classify(&mut self, movement: &Movement, tier: SafetyTier, reason: &str) {
+        match self.moves.take(movement) {
+            Some(mut x) => {
+                if tier < x.tier {
+                    x.tier = tier;
+                    debug!(
+                        "{}: set {} as {:?}, {}",
+                        self.source,
+                        movement.as_str(),
+                        tier,
+                        reason
+                    );
+                    self.ledger.push(LedgerEntry::Classified {
+                        movement: movement.to_owned(),
+                        source: self.source.to_owned(),
+                        tier,
+                        reason: reason.to_string(),
+                    });
+                }
+                self.moves.insert(x);
+            }
+            None => {
+                debug!(
+                    "{}: tried to set {} as {:?}, but {} is not a safe move",
+                    self.source,
+                    movement.as_str(),
+                    tier,
+                    movement.as_str()
+                );
+            }
+        }
+    }
+
+    pub fn score(&self, movement: &Movement) -> Option<isize> {
+        self.moves.get(movement).map(|x| x.success_score)
+    }
+
+    // Removed moves are certain death.
+    pub fn tier(&self, movement: &Movement) -> SafetyTier {
+        match self.moves.get(movement) {
+            Some(x) => x.tier,
+            None => SafetyTier::CertainDeath,
+        }
+    }
+
+    // Ties go to whichever move comes first in `Movement::all()`, so the same set always picks
+    // the same move.
     pub fn pick_movement(&self) -> Movement {
-        match self.moves.iter().max_by_key(|x| x.success_score) {
+        let order = Movement::all();
+        let rank = |movement: &Movement| order.iter().position(|x| x == movement);
+        match self
+            .moves
+            .iter()
+            .max_by_key(|x| (x.tier, x.success_score, Reverse(rank(&x.movement))))
+        {
             Some(x) => x.movement,
-            None => Movement::Up,
+            None => self.least_bad(&order),
         }
     }
+
+    // For when every move has been removed. Heuristics run roughly from the most certain checks
+    // to the least, so the move that got furthest through them before being removed is the one
+    // most likely to survive anyway. Moves removed by the same heuristic go to the first in
+    // `order`.
+    pub fn least_bad(&self, order: &[Movement]) -> Movement {
+        let stage = |movement: &Movement| {
+            let source = self.ledger.iter().find_map(|x| match x {
+                LedgerEntry::Removed {
+                    movement: removed,
+                    source,
+                    ..
+                } if removed == movement => Some(source),
+                _ => None,
+            })?;
+            self.ledger.iter().position(|x| match x {
+                LedgerEntry::Adjusted { source: other, .. }
+                | LedgerEntry::Removed { source: other, .. }
+                | LedgerEntry::Classified { source: other, .. } => other == source,
+            })
+        };
+        order
+            .iter()
+            .enumerate()
+            .max_by_key(|(rank, movement)| (stage(movement), Reverse(*rank)))
+            .map(|(_, movement)| *movement)
+            .unwrap_or(Movement::Up)
+    }
 }
 
 #[test]
 fn pick_movement_picks_highest_probability() {
     let mut movement_set = WeightedMovementSet::new();
-    movement_set.set_score(&Movement::Down, 101);
+    movement_set.set_score(&Movement::Down, 101, "test");
     assert!(movement_set.pick_movement() == Movement::Down);
-    movement_set.set_score(&Movement::Up, 102);
+    movement_set.set_score(&Movement::Up, 102, "test");
     assert!(movement_set.pick_movement() == Movement::Up);
-    movement_set.set_score(&Movement::Right, 103);
+    movement_set.set_score(&Movement::Right, 103, "test");
     assert!(movement_set.pick_movement() == Movement::Right);
-    movement_set.update_score(&Movement::Left, -1);
+    movement_set.update_score(&Movement::Left, -1, "test");
     assert!(movement_set.pick_movement() == Movement::Right);
 }
 
@@ -144,8 +343,120 @@ fn pick_movement_picks_highest_probability() {
 fn remove_removes_option() {
     let mut movement_set = WeightedMovementSet::new();
     let size = movement_set.moves.len();
-    movement_set.remove(&Movement::Down);
+    movement_set.remove(&Movement::Down, "test");
     assert!(movement_set.moves.len() == size - 1);
-    movement_set.set_score(&Movement::Down, 100);
+    movement_set.set_score(&Movement::Down, 100, "test");
     assert!(movement_set.moves.len() == size - 1);
 }
+
+#[test]
+fn ledger_records_changes_in_order_with_source() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.with_source("first", 1.0, |set| {
+        set.update_score(&Movement::Up, 20, "food");
+        set.remove(&Movement::Down, "wall");
+        set.remove(&Movement::Down, "wall again");
+    });
+    movement_set.with_source("second", 1.0, |set| {
+        set.set_score(&Movement::Up, 90, "hazard");
+        set.update_score(&Movement::Down, 20, "food");
+    });
+
+    assert_eq!(movement_set.ledger().len(), 3);
+    assert_eq!(
+        movement_set.history(&Movement::Up),
+        vec![
+            &LedgerEntry::Adjusted {
+                movement: Movement::Up,
+                source: "first".to_string(),
+                delta: 20,
+                reason: "food".to_string(),
+            },
+            &LedgerEntry::Adjusted {
+                movement: Movement::Up,
+                source: "second".to_string(),
+                delta: -30,
+                reason: "hazard".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        movement_set.history(&Movement::Down),
+        vec![&LedgerEntry::Removed {
+            movement: Movement::Down,
+            source: "first".to_string(),
+            reason: "wall".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn ledger_serializes_entries() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.with_source("avoid_bounds", 1.0, |set| {
+        set.remove(&Movement::Left, "out of bounds")
+    });
+    let ledger = serde_json::to_value(movement_set.ledger()).expect("failed to serialize");
+    assert_eq!(
+        ledger,
+        serde_json::json!([{
+            "kind": "removed",
+            "movement": "left",
+            "source": "avoid_bounds",
+            "reason": "out of bounds"
+        }])
+    );
+}
+
+#[test]
+fn pick_movement_prefers_safer_tier_over_score() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.remove(&Movement::Left, "test");
+    movement_set.remove(&Movement::Right, "test");
+    movement_set.update_score(&Movement::Up, 100, "test");
+    movement_set.classify(&Movement::Up, SafetyTier::LikelyDeath, "test");
+    movement_set.update_score(&Movement::Down, -70, "test");
+    movement_set.classify(&Movement::Down, SafetyTier::Risky, "test");
+    assert!(movement_set.pick_movement() == Movement::Down);
+
+    movement_set.classify(&Movement::Down, SafetyTier::Safe, "test");
+    assert_eq!(movement_set.tier(&Movement::Down), SafetyTier::Risky);
+    assert_eq!(movement_set.tier(&Movement::Left), SafetyTier::CertainDeath);
+    assert_eq!(movement_set.score(&Movement::Up), Some(200));
+}
+
+#[test]
+fn weighted_source_scales_score_updates() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.with_source("half", 0.5, |set| {
+        set.update_score(&Movement::Up, 20, "test");
+        set.set_score(&Movement::Down, 50, "test");
+    });
+    movement_set.update_score(&Movement::Left, 20, "test");
+    assert_eq!(movement_set.score(&Movement::Up), Some(110));
+    assert_eq!(movement_set.score(&Movement::Down), Some(50));
+    assert_eq!(movement_set.score(&Movement::Left), Some(120));
+}
+
+#[test]
+fn pick_movement_breaks_ties_and_falls_back_deterministically() {
+    let mut movement_set = WeightedMovementSet::new();
+    movement_set.update_score(&Movement::Left, 10, "test");
+    movement_set.update_score(&Movement::Right, 10, "test");
+    assert_eq!(movement_set.pick_movement(), Movement::Left);
+
+    movement_set.with_source("bounds", 1.0, |set| {
+        set.remove(&Movement::Left, "test");
+        set.remove(&Movement::Up, "test");
+    });
+    movement_set.with_source("bodies", 1.0, |set| {
+        set.remove(&Movement::Right, "test");
+        set.remove(&Movement::Down, "test");
+    });
+    // Down and right both made it past the bounds check
+    assert_eq!(movement_set.pick_movement(), Movement::Down);
+    assert_eq!(
+        movement_set.least_bad(&[Movement::Right, Movement::Down]),
+        Movement::Right
+    );
+}
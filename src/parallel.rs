@@ -0,0 +1,322 @@
+use std::cmp::Reverse;
+#[cfg(test)]
+use std::collections::HashSet;
+use std::env;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{
+    hazard::HazardLayer,
+    logging,
+    movement_set::Movement,
+    search::{Search, SearchResult, LOSS, WIN},
+    simulation::{FoodSpawning, SimState},
+    transposition::TranspositionTable,
+};
+
+#[cfg(test)]
+use crate::search::facing_longer_snake;
+
+// Workers check the clock every so many nodes, so they finish a little after the deadline.
+const DEADLINE_GRACE_MS: u64 = 20;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Threads shared by every search in the process, so however many games are being played at once
+// no more than `size` searches run at the same time. Searches that don't fit wait their turn.
+struct Pool {
+    sender: Sender<Job>,
+    size: usize,
+}
+
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+// Everything one of our moves has been searched to so far.
+struct RootMove {
+    movement: Movement,
+    // Per depth, the score and the line that goes with it
+    depths: Vec<(i32, Vec<Vec<Movement>>)>,
+    // Won or lost whatever happens, so there's no point looking deeper
+    decided: bool,
+}
+
+// `SEARCH_THREADS` sets how many threads searches are shared between, defaulting to one per core.
+fn configured_threads() -> usize {
+    env::var("SEARCH_THREADS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .filter(|x| *x > 0)
+        .or_else(|| thread::available_parallelism().ok().map(|x| x.get()))
+        .unwrap_or(1)
+}
+
+fn pool() -> &'static Pool {
+    POOL.get_or_init(|| {
+        let size = configured_threads();
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..size {
+            let receiver = Arc::clone(&receiver);
+            let spawned = thread::Builder::new()
+                .name(format!("search-{}", i))
+                .spawn(move || work(&receiver));
+            if let Err(e) = spawned {
+                error!("Failed to start search thread: {}", e);
+            }
+        }
+        Pool { sender, size }
+    })
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match receiver.lock().unwrap_or_else(|x| x.into_inner()).recv() {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        // A job that panics only loses its own result, not the thread
+        let _ = catch_unwind(AssertUnwindSafe(job));
+    }
+}
+
+pub fn pool_size() -> usize {
+    pool().size
+}
+
+// Runs `jobs` on the pool, in the log context of the caller, and collects whatever they return
+// by shortly after `deadline`. Jobs still queued or running by then are left to finish unheard.
+fn run<T: Send + 'static>(jobs: Vec<Box<dyn FnOnce() -> T + Send>>, deadline: Instant) -> Vec<T> {
+    let pool = pool();
+    let context = logging::current_context();
+    let (sender, receiver) = channel();
+    let count = jobs.len();
+    for job in jobs {
+        let sender = sender.clone();
+        let context = context.clone();
+        let job: Job = Box::new(move || {
+            let result = match context {
+                Some(context) => logging::with_context(context, job),
+                None => job(),
+            };
+            let _ = sender.send(result);
+        });
+        if pool.sender.send(job).is_err() {
+            error!("Search threads have stopped");
+        }
+    }
+    drop(sender);
+
+    let cutoff = deadline + Duration::from_millis(DEADLINE_GRACE_MS);
+    let mut results = Vec::with_capacity(count);
+    while results.len() < count {
+        match receiver.recv_timeout(cutoff.saturating_duration_since(Instant::now())) {
+            Ok(x) => results.push(x),
+            Err(_) => break,
+        }
+    }
+    if results.len() < count {
+        warn!(
+            "Only {} of {} search jobs finished in time",
+            results.len(),
+            count
+        );
+    }
+    results
+}
+
+// Splits our moves between as many jobs as the pool has threads, each deepening its share one
+// turn at a time with its own copy of `table`. Scores from different depths can't be compared,
+// so the answer comes from the deepest depth every move finished. Moves are searched with a full
+// window, which costs more nodes than `Search::deepen`, so with a single thread that's used
+// instead. Afterwards `table` holds the copy from whichever job searched the move we picked,
+// since that's the one that covers next turn. If the search comes up empty `table` is left as it
+// was, so there's still something to reuse next turn.
+pub fn parallel_deepen(
+    state: &SimState,
+    hazards: &HazardLayer,
+    spawning: FoodSpawning,
+    seeds: &[Movement],
+    deadline: Instant,
+    max_depth: u32,
+    table: &mut TranspositionTable,
+) -> Option<SearchResult> {
+    let rank = |movement: &Movement| {
+        seeds
+            .iter()
+            .position(|x| x == movement)
+            .unwrap_or(seeds.len())
+    };
+    let mut moves = state.moves(0);
+    moves.sort_by_key(rank);
+    let jobs = pool_size().clamp(1, moves.len());
+    let state = Arc::new(state.clone());
+    let hazards = Arc::new(hazards.clone());
+
+    // Every job gets its own copy of `table`, since one still running after the deadline can't
+    // hand its copy back. Sharing one table would mean taking a lock on every node. A copy is a couple of MB, which
+    // takes well under a millisecond against a search of hundreds, and the copies that don't
+    // cover the move we pick are thrown away afterwards.
+    if jobs == 1 {
+        let seeds = seeds.to_vec();
+        let copy = table.clone();
+        let job: Box<dyn FnOnce() -> _ + Send> = Box::new(move || {
+            let mut table = copy;
+            let mut search = Search::new(&state, &hazards, spawning, &mut table);
+            let result = search.deepen(&state, &seeds, deadline, max_depth);
+            search.table().log_stats();
+            (result, table)
+        });
+        let (result, searched) = run(vec![job], deadline).pop()?;
+        if result.is_some() {
+            *table = searched;
+        }
+        return result;
+    }
+
+    let jobs: Vec<Box<dyn FnOnce() -> _ + Send>> = (0..jobs)
+        .map(|job| {
+            let table = table.clone();
+            let assigned: Vec<Movement> = moves.iter().skip(job).step_by(jobs).copied().collect();
+            let state = Arc::clone(&state);
+            let hazards = Arc::clone(&hazards);
+            let spawning = spawning.clone();
+            Box::new(move || {
+                search_moves(
+                    &state, &hazards, spawning, &assigned, deadline, max_depth, table,
+                )
+            }) as Box<dyn FnOnce() -> _ + Send>
+        })
+        .collect();
+    let workers: Vec<(Vec<RootMove>, u64, TranspositionTable)> = run(jobs, deadline);
+    // A job that never reported back leaves its moves unsearched
+    if workers.iter().map(|x| x.0.len()).sum::<usize>() < moves.len() {
+        return None;
+    }
+
+    let nodes = workers.iter().map(|x| x.1).sum();
+    let roots: Vec<&RootMove> = workers.iter().flat_map(|x| &x.0).collect();
+    let depth = match roots
+        .iter()
+        .filter(|x| !x.decided)
+        .map(|x| x.depths.len())
+        .min()
+    {
+        Some(x) => x,
+        None => roots.iter().map(|x| x.depths.len()).max()?,
+    };
+    if depth == 0 {
+        return None;
+    }
+
+    // A decided move keeps its score at every depth past the one it was decided at
+    let (root, (score, pv)) = roots
+        .iter()
+        .filter_map(|x| Some((x, x.depths.get(depth - 1).or(x.depths.last())?)))
+        .max_by_key(|(x, (score, _))| (*score, Reverse(rank(&x.movement))))?;
+    let movement = root.movement;
+    let result = SearchResult {
+        movement,
+        score: *score,
+        depth: depth as u32,
+        pv: pv.clone(),
+        nodes,
+    };
+    if let Some(worker) = workers
+        .into_iter()
+        .find(|x| x.0.iter().any(|x| x.movement == movement))
+    {
+        *table = worker.2;
+    }
+    Some(result)
+}
+
+// One job's share of the search: every move it was given at one depth, then the next depth,
+// until time runs out. Returns how far each move got, how many nodes that took, and the table.
+fn search_moves(
+    state: &SimState,
+    hazards: &HazardLayer,
+    spawning: FoodSpawning,
+    moves: &[Movement],
+    deadline: Instant,
+    max_depth: u32,
+    mut table: TranspositionTable,
+) -> (Vec<RootMove>, u64, TranspositionTable) {
+    let mut search = Search::new(state, hazards, spawning, &mut table);
+    let mut roots: Vec<RootMove> = moves
+        .iter()
+        .map(|x| RootMove {
+            movement: *x,
+            depths: vec![],
+            decided: false,
+        })
+        .collect();
+
+    'deepening: for depth in 1..=max_depth {
+        if roots.iter().all(|x| x.decided) || Instant::now() >= deadline {
+            break;
+        }
+        for root in roots.iter_mut().filter(|x| !x.decided) {
+            match search.score_move(state, root.movement, depth, deadline) {
+                Some((score, line)) => {
+                    root.decided = score >= WIN || score <= LOSS;
+                    root.depths.push((score, line));
+                }
+                None => break 'deepening,
+            }
+        }
+    }
+
+    search.table().log_stats();
+    let nodes = search.nodes();
+    (roots, nodes, table)
+}
+
+#[test]
+fn parallel_deepen_avoids_head_to_head_loss() {
+    let state = facing_longer_snake();
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+
+    // Even though the heuristics would try up first
+    let deadline = Instant::now() + Duration::from_secs(3600);
+    let mut table = TranspositionTable::new(1 << 12);
+    let result = parallel_deepen(
+        &sim,
+        &hazards,
+        FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new()),
+        &[Movement::Up],
+        deadline,
+        2,
+        &mut table,
+    )
+    .expect("should finish at least one turn");
+    assert_ne!(result.movement, Movement::Up);
+    assert_eq!(result.depth, 2);
+    assert_eq!(result.pv[0][0], result.movement);
+    // The table that comes back is the copy the picked move was searched with
+    assert!(table.stats().stores > 0);
+}
+
+#[test]
+fn parallel_deepen_out_of_time_keeps_table() {
+    let state = facing_longer_snake();
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+
+    // Out of time before we start, the table we had is still there for next turn
+    let mut table = TranspositionTable::new(1 << 12);
+    let result = parallel_deepen(
+        &sim,
+        &hazards,
+        FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new()),
+        &[],
+        Instant::now(),
+        2,
+        &mut table,
+    );
+    assert!(result.is_none());
+    assert_eq!(table.size(), 1 << 12);
+}
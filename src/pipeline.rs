@@ -0,0 +1,175 @@
+use std::env;
+
+use crate::{
+    explain,
+    heuristic::{Context, Heuristic, Weighted},
+    movement_set::WeightedMovementSet,
+    response::{ExplainResponse, MoveShoutResponse},
+    selection::SelectionPolicy,
+};
+
+#[cfg(test)]
+use crate::{
+    heuristic::{AvoidBounds, ScanFood},
+    movement_set::Movement,
+    request::GameState,
+};
+
+// An ordered list of heuristics that together decide a snake's move. Snakes built from
+// heuristics are defined as one of these.
+pub struct Pipeline {
+    heuristics: Vec<Box<dyn Heuristic>>,
+    policy: SelectionPolicy,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline {
+            heuristics: vec![],
+            policy: SelectionPolicy::new(),
+        }
+    }
+
+    pub fn add<H: Heuristic + 'static>(mut self, heuristic: H) -> Pipeline {
+        self.heuristics.push(Box::new(heuristic));
+        self
+    }
+
+    // Overrides heuristic weights from `<SNAKE>_WEIGHTS`, e.g.
+    // `RUSTY_WEIGHTS=scan_food=1.5,avoid_hazards=2`, so weights can be tuned without a rebuild.
+    // The selection policy is read the same way, see `SelectionPolicy::configure`.
+    pub fn configure(self, snake: &str) -> Pipeline {
+        let pipeline = self.with_policy(SelectionPolicy::configure(snake));
+        match env::var(format!("{}_WEIGHTS", snake.to_uppercase())) {
+            Ok(weights) => pipeline.with_weights(&parse_weights(&weights)),
+            Err(_) => pipeline,
+        }
+    }
+
+    pub fn with_policy(self, policy: SelectionPolicy) -> Pipeline {
+        Pipeline { policy, ..self }
+    }
+
+    pub fn with_weights(self, weights: &[(String, f64)]) -> Pipeline {
+        Pipeline {
+            policy: self.policy,
+            heuristics: self
+                .heuristics
+                .into_iter()
+                .map(
+                    |heuristic| match weights.iter().find(|(name, _)| name == heuristic.name()) {
+                        Some((_, weight)) => Box::new(Weighted {
+                            heuristic,
+                            weight: *weight,
+                        }),
+                        None => heuristic,
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    pub fn evaluate(&self, context: &Context) -> WeightedMovementSet {
+        let mut movement_set = WeightedMovementSet::new();
+        for heuristic in &self.heuristics {
+            movement_set.with_source(heuristic.name(), heuristic.weight(), |set| {
+                heuristic.apply(context, set)
+            });
+        }
+        movement_set
+    }
+
+    pub fn get_move(&self, context: &Context) -> MoveShoutResponse {
+        let movement_set = self.evaluate(context);
+
+        debug!(
+            "Ledger: {}",
+            serde_json::to_string(movement_set.ledger()).unwrap_or_default()
+        );
+        info!("Safe moves: {:?}", movement_set.moves);
+        let chosen_move = self
+            .policy
+            .select(context, &movement_set)
+            .as_str()
+            .to_string();
+        info!("{} MOVE {}: {}", context.game.id, context.turn, chosen_move);
+        MoveShoutResponse {
+            chosen_move,
+            shout: movement_set
+                .moves
+                .into_iter()
+                .map(|x| x.movement.as_str().to_owned())
+                .collect::<Vec<String>>()
+                .join(","),
+        }
+    }
+
+    pub fn explain(&self, context: &Context) -> ExplainResponse {
+        let movement_set = self.evaluate(context);
+        info!("{} EXPLAIN {}", context.game.id, context.turn);
+        explain::explain(&movement_set, self.policy.select(context, &movement_set))
+    }
+}
+
+fn parse_weights(weights: &str) -> Vec<(String, f64)> {
+    weights
+        .split(',')
+        .filter_map(|entry| {
+            let (name, weight) = entry.split_once('=')?;
+            match weight.trim().parse() {
+                Ok(weight) => Some((name.trim().to_string(), weight)),
+                Err(_) => {
+                    warn!("Ignoring invalid heuristic weight {}", entry);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn parse_weights_skips_invalid_entries() {
+    assert_eq!(
+        parse_weights("scan_food=1.5, avoid_hazards = 2,scan_tail=lots,nonsense"),
+        vec![
+            ("scan_food".to_string(), 1.5),
+            ("avoid_hazards".to_string(), 2.0)
+        ]
+    );
+}
+
+#[test]
+fn with_weights_scales_matching_heuristic() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 11,
+            "width": 11,
+            "food": [{"x": 0, "y": 3}],
+            "hazards": [],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 0, "y": 0}, {"x": 1, "y": 0}, {"x": 2, "y": 0}],
+            "latency": "111",
+            "head": {"x": 0, "y": 0},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let context = Context::new(&state.game, &state.turn, &state.board, &state.you);
+
+    let pipeline = Pipeline::new()
+        .add(AvoidBounds)
+        .add(ScanFood)
+        .with_weights(&[("scan_food".to_string(), 2.0)]);
+    let movement_set = pipeline.evaluate(&context);
+    assert_eq!(movement_set.score(&Movement::Up), Some(160));
+    assert_eq!(movement_set.score(&Movement::Down), None);
+}
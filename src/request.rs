@@ -1,7 +1,4 @@
-use std::collections::HashMap;
-
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 // API and Response Objects
 // See https://docs.battlesnake.com/api
@@ -9,10 +6,40 @@ use serde_json::Value;
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Game {
     pub id: String,
-    pub ruleset: HashMap<String, Value>,
+    pub ruleset: Ruleset,
+    #[serde(default)]
+    pub map: Option<String>,
     pub timeout: u32,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Ruleset {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub settings: RulesetSettings,
+}
+
+// Settings the engine doesn't send, or that we don't know about yet, take the engine's defaults.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RulesetSettings {
+    pub food_spawn_chance: u32,
+    pub minimum_food: u32,
+    pub hazard_damage_per_turn: u32,
+}
+
+impl Default for RulesetSettings {
+    fn default() -> RulesetSettings {
+        RulesetSettings {
+            food_spawn_chance: 15,
+            minimum_food: 1,
+            hazard_damage_per_turn: 14,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Board {
     pub height: u32,
@@ -47,3 +74,60 @@ pub struct Battlesnake {
     pub latency: String,
     pub shout: Option<String>,
 }
+
+impl Board {
+    pub fn contains(&self, coord: &Coord) -> bool {
+        coord.x >= 0
+            && coord.x < (self.width as i32)
+            && coord.y >= 0
+            && coord.y < (self.height as i32)
+    }
+}
+
+impl GameState {
+    // Checks the assumptions the move logic makes about a board, returning a description of
+    // the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        let board = &self.board;
+        if board.width == 0 || board.height == 0 {
+            return Err(format!(
+                "board must be at least 1x1, got {}x{}",
+                board.width, board.height
+            ));
+        }
+
+        for snake in board.snakes.iter().chain(std::iter::once(&self.you)) {
+            let first = match snake.body.first() {
+                Some(x) => x,
+                None => return Err(format!("snake {} has an empty body", snake.id)),
+            };
+            if first != &snake.head {
+                return Err(format!(
+                    "snake {} has head at ({}, {}) but body starts at ({}, {})",
+                    snake.id, snake.head.x, snake.head.y, first.x, first.y
+                ));
+            }
+            if let Some(coord) = snake.body.iter().find(|x| !board.contains(x)) {
+                return Err(format!(
+                    "snake {} has a body segment at ({}, {}) outside the {}x{} board",
+                    snake.id, coord.x, coord.y, board.width, board.height
+                ));
+            }
+        }
+
+        if let Some(coord) = board.food.iter().find(|x| !board.contains(x)) {
+            return Err(format!(
+                "food at ({}, {}) is outside the {}x{} board",
+                coord.x, coord.y, board.width, board.height
+            ));
+        }
+        if let Some(coord) = board.hazards.iter().find(|x| !board.contains(x)) {
+            return Err(format!(
+                "hazard at ({}, {}) is outside the {}x{} board",
+                coord.x, coord.y, board.width, board.height
+            ));
+        }
+
+        Ok(())
+    }
+}
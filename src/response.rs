@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::movement_set::SafetyTier;
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct MoveResponse {
     #[serde(rename = "move")]
@@ -13,6 +15,39 @@ pub struct MoveShoutResponse {
     pub shout: String,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ScoreDelta {
+    pub source: String,
+    pub delta: isize,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TierChange {
+    pub source: String,
+    pub tier: SafetyTier,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct MoveExplanation {
+    #[serde(rename = "move")]
+    pub movement: String,
+    pub removed_by: Option<String>,
+    pub removal_reason: Option<String>,
+    pub score_deltas: Vec<ScoreDelta>,
+    pub tier_changes: Vec<TierChange>,
+    pub score: Option<isize>,
+    pub tier: SafetyTier,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ExplainResponse {
+    #[serde(rename = "move")]
+    pub chosen_move: String,
+    pub moves: Vec<MoveExplanation>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct InfoResponse {
     #[serde(rename = "apiversion")]
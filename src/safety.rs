@@ -0,0 +1,232 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use log::{error, warn};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::tokio::task::spawn_blocking;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    logging::{self, LogContext},
+    logic::{avoid_bounds, avoid_snake_bodies},
+    movement_set::{Movement, WeightedMovementSet},
+    request::GameState,
+    response::MoveResponse,
+};
+
+pub type MoveResult = Result<Json<Value>, status::Custom<Json<Value>>>;
+
+// Rejects malformed boards with a 422 explaining what was wrong with them.
+pub fn validate(state: &GameState) -> Result<(), status::Custom<Json<Value>>> {
+    state.validate().map_err(|reason| {
+        warn!(
+            "{} TURN {}: rejected game state, {}",
+            state.game.id, state.turn, reason
+        );
+        status::Custom(
+            Status::UnprocessableEntity,
+            Json(json!({ "error": reason })),
+        )
+    })
+}
+
+// Runs a snake's move logic against a validated game state. If the move logic panics we log
+// the offending state and still answer with the safest move we can find, since a 500 costs us
+// the game just like a timeout would.
+pub fn guard_move<T, F>(state: &GameState, get_move: F) -> MoveResult
+where
+    T: Serialize,
+    F: FnOnce() -> T,
+{
+    validate(state)?;
+
+    match catch_unwind(AssertUnwindSafe(get_move)) {
+        Ok(response) => Ok(Json(json!(response))),
+        Err(_) => {
+            error!(
+                "{} MOVE {}: move logic panicked, game state: {}",
+                state.game.id,
+                state.turn,
+                serde_json::to_string(state).unwrap_or_default()
+            );
+            let chosen_move = fallback_move(state).as_str().to_string();
+            warn!(
+                "{} MOVE {}: {} (fallback)",
+                state.game.id, state.turn, chosen_move
+            );
+            Ok(Json(json!(MoveResponse { chosen_move })))
+        }
+    }
+}
+
+// Like `guard_move` for `/explain`, except that a panic is answered with a 500. Nothing is riding
+// on the answer, and a made up move would only hide what went wrong.
+pub fn guard_explain<T, F>(state: &GameState, explain: F) -> MoveResult
+where
+    T: Serialize,
+    F: FnOnce() -> T,
+{
+    validate(state)?;
+
+    catch_unwind(AssertUnwindSafe(explain))
+        .map(|response| Json(json!(response)))
+        .map_err(|_| {
+            error!(
+                "{} EXPLAIN {}: explain logic panicked, game state: {}",
+                state.game.id,
+                state.turn,
+                serde_json::to_string(state).unwrap_or_default()
+            );
+            status::Custom(
+                Status::InternalServerError,
+                Json(json!({ "error": "explain logic panicked" })),
+            )
+        })
+}
+
+// Runs `guard_move` on a thread set aside for blocking work, so a long search doesn't hold up
+// the async workers that requests from every other game are waiting on.
+pub async fn guard_move_blocking<T, F>(
+    state: GameState,
+    context: LogContext,
+    get_move: F,
+) -> MoveResult
+where
+    T: Serialize,
+    F: FnOnce(&GameState) -> T + Send + 'static,
+{
+    run_blocking(state, context, "move", move |state| {
+        guard_move(state, || get_move(state))
+    })
+    .await
+}
+
+// The same for `guard_explain`, since explaining a turn runs the same heuristics as playing it.
+pub async fn guard_explain_blocking<T, F>(
+    state: GameState,
+    context: LogContext,
+    explain: F,
+) -> MoveResult
+where
+    T: Serialize,
+    F: FnOnce(&GameState) -> T + Send + 'static,
+{
+    run_blocking(state, context, "explain", move |state| {
+        guard_explain(state, || explain(state))
+    })
+    .await
+}
+
+async fn run_blocking<F>(
+    state: GameState,
+    context: LogContext,
+    name: &str,
+    guarded: F,
+) -> MoveResult
+where
+    F: FnOnce(&GameState) -> MoveResult + Send + 'static,
+{
+    spawn_blocking(move || logging::with_context(context, || guarded(&state)))
+        .await
+        .unwrap_or_else(|e| {
+            error!("{} task failed: {}", name, e);
+            Err(status::Custom(
+                Status::InternalServerError,
+                Json(json!({ "error": format!("{} task failed", name) })),
+            ))
+        })
+}
+
+// Only the checks that can't be wrong about certain death, so this is as unlikely to fail as
+// whatever caused us to get here.
+pub fn fallback_move(state: &GameState) -> Movement {
+    let board = &state.board;
+    let you = &state.you;
+    catch_unwind(|| {
+        let mut movement_set = WeightedMovementSet::new();
+        avoid_bounds(board.width, board.height, you, &mut movement_set);
+        avoid_snake_bodies(&board.snakes, you, &mut movement_set);
+        movement_set.pick_movement()
+    })
+    .unwrap_or(Movement::Up)
+}
+
+#[cfg(test)]
+fn cornered_state() -> GameState {
+    serde_json::from_str(
+        r#"{
+          "game": {
+            "id": "unique-game-id",
+            "ruleset": {
+              "name": "standard"
+            },
+            "timeout": 500
+          },
+          "turn": 0,
+          "board": {
+            "height": 11,
+            "width": 11,
+            "food": [],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "my-snake",
+                "name": "My Snake",
+                "health": 54,
+                "body": [
+                  {"x": 0, "y": 0},
+                  {"x": 1, "y": 0},
+                  {"x": 2, "y": 0}
+                ],
+                "latency": "111",
+                "head": {"x": 0, "y": 0},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [
+              {"x": 0, "y": 0},
+              {"x": 1, "y": 0},
+              {"x": 2, "y": 0}
+            ],
+            "latency": "111",
+            "head": {"x": 0, "y": 0},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state")
+}
+
+#[test]
+fn guard_move_falls_back_to_safe_move_on_panic() {
+    let state = cornered_state();
+    let response = guard_move(&state, || -> MoveResponse { panic!("bad move logic") })
+        .expect("expected a move response");
+    assert_eq!(response.0["move"], "up");
+}
+
+#[test]
+fn guard_explain_reports_panic() {
+    let state = cornered_state();
+    let response = guard_explain(&state, || -> MoveResponse { panic!("bad explain logic") });
+    let failure = response.expect_err("expected the panic to be reported");
+    assert_eq!(failure.0, Status::InternalServerError);
+}
+
+#[test]
+fn guard_move_rejects_invalid_state() {
+    let mut state = cornered_state();
+    state.you.body.clear();
+    let response = guard_move(&state, || MoveResponse {
+        chosen_move: "up".to_string(),
+    });
+    let rejection = response.expect_err("expected invalid state to be rejected");
+    assert_eq!(rejection.0, Status::UnprocessableEntity);
+}
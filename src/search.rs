@@ -0,0 +1,716 @@
+use std::cmp::Reverse;
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+use crate::{
+    graph::get_adjacent_nodes,
+    hazard::HazardLayer,
+    movement_set::{Movement, WeightedMovementSet},
+    request::Coord,
+    simulation::{FoodSpawning, SimState},
+    transposition::{Bound, TranspositionTable},
+    zobrist::Zobrist,
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+pub const WIN: i32 = 1_000_000;
+pub const LOSS: i32 = -WIN;
+
+const AREA_SCORE: i32 = 10;
+const LENGTH_SCORE: i32 = 50;
+const OPPONENT_SCORE: i32 = 200;
+
+// Iterative deepening stops here even with time to spare.
+pub const MAX_DEPTH: u32 = 64;
+// Looking at the clock on every node would cost more than it saves.
+const CLOCK_INTERVAL: u64 = 64;
+// Placements tried for each way food could spawn. Every one multiplies the work.
+const SPAWN_SAMPLES: usize = 1;
+// Turns whose food spawns are averaged over. Every one of them doubles the work of everything
+// after it, so deeper than this only the first way food could spawn is searched.
+const SPAWN_PLIES: usize = 2;
+
+// The answer from the deepest search that finished.
+pub struct SearchResult {
+    pub movement: Movement,
+    pub score: i32,
+    pub depth: u32,
+    // The line the search expects to be played, one turn per entry with our move first
+    pub pv: Vec<Vec<Movement>>,
+    // Positions searched across every depth
+    pub nodes: u64,
+}
+
+// A depth-limited paranoid search: we pick the move that does best against whatever combination
+// of opponent moves is worst for us. Each level of depth is one full turn, with our move and
+// every opponent's move played at once, followed by the engine spawning food, which is averaged
+// over the ways it could go.
+pub struct Search<'a> {
+    hazards: &'a HazardLayer,
+    spawning: FoodSpawning,
+    zobrist: Zobrist,
+    table: &'a mut TranspositionTable,
+    width: u32,
+    deadline: Option<Instant>,
+    aborted: bool,
+    // Per ply, the last two of our moves that caused a cutoff
+    killers: Vec<[Option<Movement>; 2]>,
+    // Per square and move, how much cutting off from there has been worth
+    history: Vec<u64>,
+    // Per ply, the best line found from there
+    pv: Vec<Vec<Vec<Movement>>>,
+    nodes: u64,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(
+        state: &SimState,
+        hazards: &'a HazardLayer,
+        spawning: FoodSpawning,
+        table: &'a mut TranspositionTable,
+    ) -> Search<'a> {
+        table.new_search();
+        Search {
+            hazards,
+            spawning,
+            zobrist: Zobrist::new(state, hazards),
+            table,
+            width: state.width,
+            deadline: None,
+            aborted: false,
+            killers: vec![],
+            history: vec![0; (state.width * state.height) as usize * 4],
+            pv: vec![],
+            nodes: 0,
+        }
+    }
+
+    // Searches one turn deeper at a time until `deadline` or `max_depth`, so there's always a
+    // finished answer to fall back on. Each depth tries the best move from the one before first,
+    // then `seeds` in order. The heuristics behind `seeds` only looked at `state`, so they only
+    // order our first move; deeper turns are ordered by the table, killers and history alone.
+    // None if not even one turn could be searched in time.
+    pub fn deepen(
+        &mut self,
+        state: &SimState,
+        seeds: &[Movement],
+        deadline: Instant,
+        max_depth: u32,
+    ) -> Option<SearchResult> {
+        self.deadline = Some(deadline);
+        let mut result = None;
+        for depth in 1..=max_depth.min(MAX_DEPTH) {
+            if Instant::now() >= deadline {
+                break;
+            }
+            let (movement, score) = match self.best_move(state, depth, seeds) {
+                Some(x) => x,
+                None => break,
+            };
+            let pv = self.pv.first().cloned().unwrap_or_default();
+            debug!(
+                "Depth {}: {} scores {} after {} nodes, line {}",
+                depth,
+                movement.as_str(),
+                score,
+                self.nodes,
+                format_line(&pv)
+            );
+            result = Some(SearchResult {
+                movement,
+                score,
+                depth,
+                pv,
+                nodes: self.nodes,
+            });
+            // Once every line is decided, searching deeper won't change anything
+            if score >= WIN || score <= LOSS {
+                break;
+            }
+        }
+        result
+    }
+
+    // Our best move from `state` looking `depth` turns ahead, and what it's worth. None if we
+    // ran out of time first.
+    pub fn best_move(
+        &mut self,
+        state: &SimState,
+        depth: u32,
+        seeds: &[Movement],
+    ) -> Option<(Movement, i32)> {
+        self.aborted = false;
+        self.ensure_ply(0);
+        let key = self.zobrist.hash(state);
+        let previous = self.table.probe(key).and_then(|x| x.best);
+
+        let mut best: Option<(Movement, i32)> = None;
+        let mut alpha = LOSS - depth as i32 - 1;
+        let beta = WIN + depth as i32 + 1;
+        for movement in self.order_moves(state, 0, previous, seeds) {
+            let (score, line) = self.opponents_reply(state, movement, depth, 0, alpha, beta);
+            if self.aborted {
+                return None;
+            }
+            if best.is_none_or(|x| score > x.1) {
+                best = Some((movement, score));
+                self.pv[0] = line;
+            }
+            alpha = alpha.max(score);
+        }
+        let (movement, score) = best?;
+        self.table
+            .store(key, depth, score, Bound::Exact, Some(movement));
+        Some((movement, score))
+    }
+
+    // What `movement` from `state` is worth looking `depth` turns ahead, and the line that
+    // follows. None if we ran out of time first.
+    pub fn score_move(
+        &mut self,
+        state: &SimState,
+        movement: Movement,
+        depth: u32,
+        deadline: Instant,
+    ) -> Option<(i32, Vec<Vec<Movement>>)> {
+        self.deadline = Some(deadline);
+        self.aborted = false;
+        self.ensure_ply(0);
+        let alpha = LOSS - depth as i32 - 1;
+        let beta = WIN + depth as i32 + 1;
+        let result = self.opponents_reply(state, movement, depth, 0, alpha, beta);
+        if self.aborted {
+            return None;
+        }
+        Some(result)
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn table(&self) -> &TranspositionTable {
+        self.table
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if let Some(deadline) = self.deadline {
+            if self.nodes.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    fn ensure_ply(&mut self, ply: usize) {
+        if self.pv.len() <= ply {
+            self.pv.resize(ply + 1, vec![]);
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        self.pv[ply].clear();
+    }
+
+    fn history_index(&self, state: &SimState, movement: &Movement) -> usize {
+        let head = state.snakes[0].head();
+        let square = (head.y * self.width as i32 + head.x) as usize;
+        let direction = Movement::all()
+            .iter()
+            .position(|x| x == movement)
+            .unwrap_or(0);
+        square * 4 + direction
+    }
+
+    // Our moves from `state`, starting with the best one from the last time we searched it,
+    // then ones that caused cutoffs elsewhere at this ply, then whichever `seeds` ranks first,
+    // then whatever has caused the most cutoffs from this square overall.
+    fn order_moves(
+        &self,
+        state: &SimState,
+        ply: usize,
+        previous: Option<Movement>,
+        seeds: &[Movement],
+    ) -> Vec<Movement> {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        let mut moves = state.moves(0);
+        moves.sort_by_key(|movement| {
+            (
+                Reverse(previous == Some(*movement)),
+                Reverse(killers.contains(&Some(*movement))),
+                seeds
+                    .iter()
+                    .position(|x| x == movement)
+                    .unwrap_or(seeds.len()),
+                Reverse(self.history[self.history_index(state, movement)]),
+            )
+        });
+        moves
+    }
+
+    fn record_cutoff(&mut self, state: &SimState, ply: usize, depth: u32, movement: Movement) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(movement) {
+            killers[1] = killers[0];
+            killers[0] = Some(movement);
+        }
+        let index = self.history_index(state, &movement);
+        self.history[index] += (depth * depth) as u64;
+    }
+
+    // The value of a position where it's our turn to move.
+    fn our_move(
+        &mut self,
+        state: &SimState,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return 0;
+        }
+        self.ensure_ply(ply);
+        if state.is_over() || depth == 0 {
+            return evaluate(state, depth);
+        }
+
+        let key = self.zobrist.hash(state);
+        let entry = self.table.probe(key);
+        if let Some(entry) = entry.filter(|x| x.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => return entry.score,
+                Bound::Lower => alpha = alpha.max(entry.score),
+                Bound::Upper => beta = beta.min(entry.score),
+            }
+            if alpha >= beta {
+                return entry.score;
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best = (None, i32::MIN);
+        // Seeds only cover the root, see `deepen`
+        for movement in self.order_moves(state, ply, entry.and_then(|x| x.best), &[]) {
+            let (score, line) = self.opponents_reply(state, movement, depth, ply, alpha, beta);
+            if self.aborted {
+                return 0;
+            }
+            if score > best.1 {
+                best = (Some(movement), score);
+                self.pv[ply] = line;
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                self.record_cutoff(state, ply, depth, movement);
+                break;
+            }
+        }
+
+        let bound = if best.1 <= original_alpha {
+            Bound::Upper
+        } else if best.1 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(key, depth, best.1, bound, best.0);
+        best.1
+    }
+
+    // The value of making `movement` from `state`, assuming the opponents answer with whatever
+    // hurts us most, along with the line that follows.
+    fn opponents_reply(
+        &mut self,
+        state: &SimState,
+        movement: Movement,
+        depth: u32,
+        ply: usize,
+        alpha: i32,
+        mut beta: i32,
+    ) -> (i32, Vec<Vec<Movement>>) {
+        let mut worst = (i32::MAX, vec![]);
+        for replies in joint_moves(state) {
+            let mut moves = vec![movement];
+            moves.extend(replies);
+            let mut next = state.clone();
+            next.advance(&moves, self.hazards);
+            let score = self.spawn_food(&next, depth - 1, ply + 1, alpha, beta);
+            if self.aborted {
+                break;
+            }
+            if score < worst.0 {
+                let mut line = vec![moves];
+                line.extend(self.pv[ply + 1].iter().cloned());
+                worst = (score, line);
+            }
+            beta = beta.min(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        worst
+    }
+
+    // The value of `state` once the engine has spawned food, averaged over the ways it could
+    // spawn for the first `SPAWN_PLIES` turns. Each outcome is searched with a window narrowed so
+    // that the average cuts off as soon as it can't end up between `alpha` and `beta`. The line
+    // kept is the first outcome's, which is no food spawning unless some has to.
+    fn spawn_food(
+        &mut self,
+        state: &SimState,
+        depth: u32,
+        ply: usize,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        // Food spawned now can't be eaten before the search ends
+        if depth == 0 || state.is_over() {
+            return self.our_move(state, depth, ply, alpha, beta);
+        }
+        let mut outcomes = self
+            .spawning
+            .outcomes(state, SPAWN_SAMPLES, self.zobrist.hash(state));
+        if ply > SPAWN_PLIES {
+            outcomes.truncate(1);
+        }
+        if let [(_, food)] = &outcomes[..] {
+            let mut next = state.clone();
+            next.food.extend(food);
+            return self.our_move(&next, depth, ply, alpha, beta);
+        }
+
+        let lower = (LOSS - MAX_DEPTH as i32) as f64;
+        let upper = (WIN + MAX_DEPTH as i32) as f64;
+        let (alpha, beta) = (alpha as f64, beta as f64);
+        let mut total = 0.0;
+        let mut remaining = 1.0;
+        let mut line = None;
+        for (probability, food) in outcomes {
+            remaining -= probability;
+            let child_alpha = ((alpha - total - upper * remaining) / probability).floor();
+            let child_beta = ((beta - total - lower * remaining) / probability).ceil();
+            let mut next = state.clone();
+            next.food.extend(food);
+            let score = self.our_move(
+                &next,
+                depth,
+                ply,
+                child_alpha.max(lower) as i32,
+                child_beta.min(upper) as i32,
+            );
+            if self.aborted {
+                return 0;
+            }
+            if line.is_none() {
+                line = Some(self.pv[ply].clone());
+            }
+            total += probability * score as f64;
+
+            if total + lower * remaining >= beta {
+                return (total + lower * remaining).floor().max(beta) as i32;
+            }
+            if total + upper * remaining <= alpha {
+                return (total + upper * remaining).ceil().min(alpha) as i32;
+            }
+        }
+        if let Some(line) = line {
+            self.pv[ply] = line;
+        }
+        total.round() as i32
+    }
+}
+
+// How deep an earlier search looked from `state`, if it got there at all. When it did, `table`
+// already holds the subtree we're about to search.
+pub fn searched_depth(
+    table: &mut TranspositionTable,
+    state: &SimState,
+    hazards: &HazardLayer,
+) -> Option<u32> {
+    let key = Zobrist::new(state, hazards).hash(state);
+    table.probe(key).map(|x| x.depth)
+}
+
+// Moves from best to worst by the heuristics' reckoning, with the ones they ruled out last.
+pub fn heuristic_order(set: &WeightedMovementSet) -> Vec<Movement> {
+    let mut moves = Movement::all().to_vec();
+    moves.sort_by_key(|movement| {
+        Reverse(set.moves.get(movement).map(|x| (x.tier, x.success_score)))
+    });
+    moves
+}
+
+// A line of play as e.g. `up/left, right/down`, with our move first in each turn.
+pub fn format_line(line: &[Vec<Movement>]) -> String {
+    line.iter()
+        .map(|turn| {
+            turn.iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Every combination of moves the opponents could make.
+fn joint_moves(state: &SimState) -> Vec<Vec<Movement>> {
+    let mut combinations = vec![vec![]];
+    for i in 1..state.snakes.len() {
+        let moves = state.moves(i);
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination: Vec<Movement>| {
+                moves.iter().map(move |x| {
+                    let mut next = combination.clone();
+                    next.push(*x);
+                    next
+                })
+            })
+            .collect();
+    }
+    combinations
+}
+
+// How good a position is for us. Dying sooner is worse than dying later, and winning sooner is
+// better than winning later, so `depth` (the turns left to search) nudges the score either way.
+// Otherwise we want room to move, to be longer than everyone else, and fewer opponents.
+pub fn evaluate(state: &SimState, depth: u32) -> i32 {
+    if !state.snakes[0].alive {
+        return LOSS - depth as i32;
+    }
+    if state.is_over() {
+        return WIN + depth as i32;
+    }
+
+    let you = &state.snakes[0];
+    let opponents: Vec<_> = state.snakes[1..].iter().filter(|x| x.alive).collect();
+    let longest = opponents.iter().map(|x| x.body.len()).max().unwrap_or(0);
+    let area = reachable_area(state, &you.head(), you.body.len() * 2);
+
+    AREA_SCORE * area as i32 + LENGTH_SCORE * (you.body.len() as i32 - longest as i32).clamp(-5, 5)
+        - OPPONENT_SCORE * opponents.len() as i32
+        + you.health as i32 / 10
+}
+
+// Squares reachable from `start` without crossing a body, up to `enough`.
+fn reachable_area(state: &SimState, start: &Coord, enough: usize) -> usize {
+    let blocked: HashSet<Coord> = state
+        .snakes
+        .iter()
+        .filter(|x| x.alive)
+        .flat_map(|x| x.body.iter().take(x.body.len() - 1).copied())
+        .collect();
+    let mut visited = HashSet::from([*start]);
+    let mut queue = VecDeque::from([*start]);
+    let mut area = 0;
+    while let Some(coord) = queue.pop_front() {
+        for node in get_adjacent_nodes(&coord) {
+            if state.contains(&node.coord)
+                && !blocked.contains(&node.coord)
+                && visited.insert(node.coord)
+            {
+                area += 1;
+                if area >= enough {
+                    return area;
+                }
+                queue.push_back(node.coord);
+            }
+        }
+    }
+    area
+}
+
+// We're a move below a longer snake's head, so moving up could meet it head-to-head. There's
+// enough food that none has to spawn, so the turn after is the one the search looked at.
+#[cfg(test)]
+pub fn facing_longer_snake() -> GameState {
+    serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 7,
+            "width": 7,
+            "food": [{"x": 0, "y": 6}],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 3, "y": 5}, {"x": 4, "y": 5}, {"x": 5, "y": 5}, {"x": 6, "y": 5}],
+                "latency": "111",
+                "head": {"x": 3, "y": 5},
+                "length": 4
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 3, "y": 3}, {"x": 3, "y": 2}, {"x": 3, "y": 1}],
+            "latency": "111",
+            "head": {"x": 3, "y": 3},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state")
+}
+
+#[test]
+fn search_sees_head_to_head_loss() {
+    let state = facing_longer_snake();
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+    let mut table = TranspositionTable::new(1 << 12);
+    let mut search = Search::new(
+        &sim,
+        &hazards,
+        FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new()),
+        &mut table,
+    );
+
+    // Moving up could meet the longer snake's head, so anything else is better
+    let (movement, score) = search
+        .best_move(&sim, 2, &[])
+        .expect("no deadline to run out of");
+    assert_ne!(movement, Movement::Up);
+    assert!(score > LOSS);
+
+    // Searching again a turn deeper finds everything from the first search in the table
+    let (movement, _) = search
+        .best_move(&sim, 3, &[])
+        .expect("no deadline to run out of");
+    assert_ne!(movement, Movement::Up);
+    assert!(search.table().stats().hits > 0);
+}
+
+#[test]
+fn deepen_keeps_last_finished_depth() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 5,
+            "width": 5,
+            "food": [],
+            "hazards": [],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 0, "y": 1}, {"x": 1, "y": 1}, {"x": 2, "y": 1}],
+            "latency": "111",
+            "head": {"x": 0, "y": 1},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+    let mut table = TranspositionTable::new(1 << 12);
+    let mut search = Search::new(
+        &sim,
+        &hazards,
+        FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new()),
+        &mut table,
+    );
+
+    // Out of time before we start, there's nothing to show for it
+    assert!(search
+        .deepen(&sim, &[], Instant::now(), MAX_DEPTH)
+        .is_none());
+
+    let deadline = Instant::now() + std::time::Duration::from_secs(3600);
+    let result = search
+        .deepen(&sim, &[Movement::Down, Movement::Up], deadline, 3)
+        .expect("should finish at least one turn");
+    assert_eq!(result.depth, 3);
+    // Lines stop early where the table already had the answer
+    assert!(!result.pv.is_empty() && result.pv.len() <= result.depth as usize);
+    assert_eq!(result.pv[0], vec![result.movement]);
+}
+
+#[test]
+fn next_turn_reuses_table() {
+    let state = facing_longer_snake();
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+    let mut table = TranspositionTable::new(1 << 14);
+    let deadline = Instant::now() + std::time::Duration::from_secs(3600);
+    let result = Search::new(
+        &sim,
+        &hazards,
+        FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new()),
+        &mut table,
+    )
+    .deepen(&sim, &[], deadline, 3)
+    .expect("should finish at least one turn");
+
+    // Play the turn the search expected, and it's already been searched from there
+    let mut next = sim.clone();
+    next.advance(&result.pv[0], &hazards);
+    let depth = searched_depth(&mut table, &next, &hazards).expect("should be in the table");
+    assert!(depth + 1 >= result.depth);
+}
+
+#[test]
+fn spawned_food_can_save_a_starving_snake() {
+    // We fill three squares of a 2x2 board, so the only free square after our first move is
+    // where food has to spawn for us to eat before starving on the second.
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {
+            "id": "unique-game-id",
+            "ruleset": {"name": "solo", "settings": {"foodSpawnChance": 50, "minimumFood": 0}},
+            "timeout": 500
+          },
+          "turn": 0,
+          "board": {
+            "height": 2,
+            "width": 2,
+            "food": [],
+            "hazards": [],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 2,
+            "body": [{"x": 0, "y": 0}, {"x": 0, "y": 1}, {"x": 1, "y": 1}],
+            "latency": "111",
+            "head": {"x": 0, "y": 0},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let hazards = HazardLayer::new(&state.board, 14);
+    let sim = SimState::new(&state.board, &state.you);
+    let score = |chance| {
+        let mut spawning = FoodSpawning::new(&state.game.ruleset.settings, &HashSet::new());
+        spawning.chance = chance;
+        let mut table = TranspositionTable::new(1 << 12);
+        Search::new(&sim, &hazards, spawning, &mut table)
+            .best_move(&sim, 3, &[])
+            .expect("no deadline to run out of")
+            .1
+    };
+
+    let never = score(0);
+    let sometimes = score(50);
+    let always = score(100);
+    assert!(never <= LOSS);
+    assert!(always > LOSS);
+    // An even chance of being saved lands in between
+    assert!(never < sometimes && sometimes < always);
+}
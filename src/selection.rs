@@ -0,0 +1,257 @@
+use std::cmp::Reverse;
+use std::env;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    graph::{get_adjacent_nodes, manhattan_distance},
+    heuristic::Context,
+    movement_set::{Movement, WeightedMovementSet},
+    request::Coord,
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+// Which of two equally scored moves to take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    // Whichever comes first in `Movement::all()`
+    Fixed,
+    Center,
+    AwayFromOpponents,
+}
+
+impl TieBreak {
+    fn parse(name: &str) -> Option<TieBreak> {
+        match name.trim() {
+            "fixed" => Some(TieBreak::Fixed),
+            "center" => Some(TieBreak::Center),
+            "away_from_opponents" => Some(TieBreak::AwayFromOpponents),
+            _ => None,
+        }
+    }
+}
+
+// How a snake picks its move from what its heuristics left. Only moves in the safest tier that's
+// left are considered. By default the best scored of those is taken, with ties broken by
+// `tie_break`. With a `temperature` the move is sampled instead, more evenly the higher it is,
+// from a seed that only depends on the game and turn so replays pick the same moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionPolicy {
+    pub tie_break: TieBreak,
+    pub temperature: Option<f64>,
+}
+
+impl SelectionPolicy {
+    pub fn new() -> SelectionPolicy {
+        SelectionPolicy {
+            tie_break: TieBreak::Center,
+            temperature: None,
+        }
+    }
+
+    // Reads `<SNAKE>_TIE_BREAK`, one of `fixed`, `center` or `away_from_opponents`, and
+    // `<SNAKE>_TEMPERATURE`.
+    pub fn configure(snake: &str) -> SelectionPolicy {
+        let mut policy = SelectionPolicy::new();
+        let snake = snake.to_uppercase();
+        if let Ok(name) = env::var(format!("{}_TIE_BREAK", snake)) {
+            match TieBreak::parse(&name) {
+                Some(x) => policy.tie_break = x,
+                None => warn!("Ignoring invalid tie break {}", name),
+            }
+        }
+        if let Ok(temperature) = env::var(format!("{}_TEMPERATURE", snake)) {
+            match temperature.trim().parse::<f64>() {
+                Ok(x) if x > 0.0 => policy.temperature = Some(x),
+                _ => warn!("Ignoring invalid temperature {}", temperature),
+            }
+        }
+        policy
+    }
+
+    // Every move, most preferred first when scores are tied.
+    pub fn order(&self, context: &Context) -> Vec<Movement> {
+        let head = &context.you.head;
+        let next = |movement: &Movement| {
+            get_adjacent_nodes(head)
+                .into_iter()
+                .find(|x| &x.movement == movement)
+                .map(|x| x.coord)
+                .unwrap_or(*head)
+        };
+        let mut moves = Movement::all().to_vec();
+        match self.tie_break {
+            TieBreak::Fixed => {}
+            TieBreak::Center => moves.sort_by_key(|x| from_center(context, &next(x))),
+            TieBreak::AwayFromOpponents => {
+                moves.sort_by_key(|x| Reverse(nearest_opponent(context, &next(x))))
+            }
+        }
+        moves
+    }
+
+    pub fn select(&self, context: &Context, set: &WeightedMovementSet) -> Movement {
+        let order = self.order(context);
+        let tier = match set.moves.iter().map(|x| x.tier).max() {
+            Some(x) => x,
+            None => return set.least_bad(&order),
+        };
+        // Scores paired with moves in order of preference, so iterating is deterministic
+        let candidates: Vec<(Movement, isize)> = order
+            .iter()
+            .filter_map(|movement| {
+                set.moves
+                    .get(movement)
+                    .filter(|x| x.tier == tier)
+                    .map(|x| (*movement, x.success_score))
+            })
+            .collect();
+
+        match self.temperature {
+            Some(temperature) => sample(&candidates, temperature, seed(context)),
+            None => {
+                // `max_by_key` keeps the last maximum, so go from least preferred
+                candidates
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, score)| *score)
+                    .map(|(movement, _)| *movement)
+                    .unwrap_or_else(|| set.least_bad(&order))
+            }
+        }
+    }
+}
+
+// Twice the distance from the middle of the board, so boards with an even side don't need
+// fractions.
+fn from_center(context: &Context, coord: &Coord) -> i32 {
+    let width = context.board.width as i32;
+    let height = context.board.height as i32;
+    (2 * coord.x - (width - 1)).abs() + (2 * coord.y - (height - 1)).abs()
+}
+
+fn nearest_opponent(context: &Context, coord: &Coord) -> usize {
+    context
+        .board
+        .snakes
+        .iter()
+        .filter(|x| x.id != context.you.id)
+        .map(|x| manhattan_distance(coord, &x.head))
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+// FNV-1a over the game ID and turn. The standard library's hasher isn't promised to stay the
+// same between releases, which would change which moves replays pick.
+fn seed(context: &Context) -> u64 {
+    context
+        .game
+        .id
+        .bytes()
+        .chain(context.turn.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// Softmax over the scores, so a move `temperature` points behind another is about a third as
+// likely to be picked.
+fn sample(candidates: &[(Movement, isize)], temperature: f64, seed: u64) -> Movement {
+    let best = match candidates.iter().map(|x| x.1).max() {
+        Some(x) => x,
+        None => return Movement::Up,
+    };
+    let weights: Vec<f64> = candidates
+        .iter()
+        .map(|(_, score)| ((score - best) as f64 / temperature).exp())
+        .collect();
+    let mut pick = StdRng::seed_from_u64(seed).gen::<f64>() * weights.iter().sum::<f64>();
+    for ((movement, _), weight) in candidates.iter().zip(&weights) {
+        if pick < *weight {
+            return *movement;
+        }
+        pick -= weight;
+    }
+    candidates[candidates.len() - 1].0
+}
+
+#[cfg(test)]
+fn open_board() -> GameState {
+    serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 3,
+          "board": {
+            "height": 7,
+            "width": 7,
+            "food": [],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 0, "y": 4}, {"x": 0, "y": 5}, {"x": 0, "y": 6}],
+                "latency": "111",
+                "head": {"x": 0, "y": 4},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 1}, {"x": 1, "y": 0}],
+            "latency": "111",
+            "head": {"x": 1, "y": 1},
+            "length": 2
+          }
+        }"#,
+    )
+    .expect("failed to parse game state")
+}
+
+#[test]
+fn ties_are_broken_by_preference() {
+    let state = open_board();
+    let context = Context::new(&state.game, &state.turn, &state.board, &state.you);
+    let mut set = WeightedMovementSet::new();
+    set.remove(&Movement::Down, "test");
+
+    let mut policy = SelectionPolicy::new();
+    // Up and right are both a step closer to the middle, and up comes first
+    assert_eq!(policy.select(&context, &set), Movement::Up);
+    policy.tie_break = TieBreak::AwayFromOpponents;
+    assert_eq!(policy.select(&context, &set), Movement::Right);
+    // Scores still come first
+    set.update_score(&Movement::Left, 1, "test");
+    assert_eq!(policy.select(&context, &set), Movement::Left);
+}
+
+#[test]
+fn sampling_is_seeded_by_game_and_turn() {
+    let state = open_board();
+    let context = Context::new(&state.game, &state.turn, &state.board, &state.you);
+    let mut set = WeightedMovementSet::new();
+    set.remove(&Movement::Down, "test");
+    let policy = SelectionPolicy {
+        tie_break: TieBreak::Fixed,
+        temperature: Some(50.0),
+    };
+
+    let picked = policy.select(&context, &set);
+    assert_ne!(picked, Movement::Down);
+    for _ in 0..10 {
+        assert_eq!(policy.select(&context, &set), picked);
+    }
+    // With next to no temperature it's the same as picking the best
+    set.update_score(&Movement::Right, 20, "test");
+    let cold = SelectionPolicy {
+        temperature: Some(0.01),
+        ..policy
+    };
+    assert_eq!(cold.select(&context, &set), Movement::Right);
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+use crate::{
+    graph::get_adjacent_nodes,
+    hazard::HazardLayer,
+    movement_set::Movement,
+    request::{Battlesnake, Board, Coord, RulesetSettings},
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+#[derive(Clone, Debug)]
+pub struct SimSnake {
+    pub health: u32,
+    pub body: VecDeque<Coord>,
+    pub alive: bool,
+}
+
+impl SimSnake {
+    pub fn head(&self) -> Coord {
+        self.body[0]
+    }
+
+    // A snake that ate last turn has its tail doubled up, and the tail won't move this turn.
+    fn tail_stays(&self) -> bool {
+        let len = self.body.len();
+        len > 1 && self.body[len - 1] == self.body[len - 2]
+    }
+}
+
+// A copy of the board that can be played forward under the standard rules. We're always the
+// first snake. Hazards don't change during a lookahead, so they're kept separately in a
+// `HazardLayer`.
+#[derive(Clone, Debug)]
+pub struct SimState {
+    pub width: u32,
+    pub height: u32,
+    pub snakes: Vec<SimSnake>,
+    pub food: Vec<Coord>,
+}
+
+impl SimState {
+    pub fn new(board: &Board, you: &Battlesnake) -> SimState {
+        let snakes = std::iter::once(you)
+            .chain(board.snakes.iter().filter(|x| x.id != you.id))
+            .map(|x| SimSnake {
+                health: x.health,
+                body: x.body.iter().copied().collect(),
+                alive: true,
+            })
+            .collect();
+        SimState {
+            width: board.width,
+            height: board.height,
+            snakes,
+            food: board.food.clone(),
+        }
+    }
+
+    pub fn contains(&self, coord: &Coord) -> bool {
+        coord.x >= 0 && coord.y >= 0 && coord.x < self.width as i32 && coord.y < self.height as i32
+    }
+
+    // Squares with nothing on them, where food could spawn.
+    pub fn empty_squares(&self) -> Vec<Coord> {
+        let occupied: HashSet<Coord> = self
+            .snakes
+            .iter()
+            .filter(|x| x.alive)
+            .flat_map(|x| x.body.iter().copied())
+            .chain(self.food.iter().copied())
+            .collect();
+        (0..self.height as i32)
+            .flat_map(|y| (0..self.width as i32).map(move |x| Coord { x, y }))
+            .filter(|x| !occupied.contains(x))
+            .collect()
+    }
+
+    // The game is over for us once we're dead, or once we're the last snake standing in a game
+    // that had opponents.
+    pub fn is_over(&self) -> bool {
+        !self.snakes[0].alive
+            || (self.snakes.len() > 1 && self.snakes[1..].iter().all(|x| !x.alive))
+    }
+
+    // The moves snake `index` could make without dying to walls or bodies straight away. A snake
+    // with nowhere to go still has to move somewhere, so it gets `Up`.
+    pub fn moves(&self, index: usize) -> Vec<Movement> {
+        let snake = &self.snakes[index];
+        if !snake.alive {
+            return vec![Movement::Up];
+        }
+        let moves: Vec<Movement> = get_adjacent_nodes(&snake.head())
+            .into_iter()
+            .filter(|x| self.contains(&x.coord) && !self.blocked(&x.coord))
+            .map(|x| x.movement)
+            .collect();
+        if moves.is_empty() {
+            return vec![Movement::Up];
+        }
+        moves
+    }
+
+    // Whether a body will still be on `coord` after everyone moves.
+    fn blocked(&self, coord: &Coord) -> bool {
+        self.snakes.iter().filter(|x| x.alive).any(|snake| {
+            let len = if snake.tail_stays() {
+                snake.body.len()
+            } else {
+                snake.body.len() - 1
+            };
+            snake.body.iter().take(len).any(|x| x == coord)
+        })
+    }
+
+    // Plays one turn, with `moves[i]` being the move of snake `i`: move, lose health, eat, then
+    // eliminate, the same order the engine uses.
+    pub fn advance(&mut self, moves: &[Movement], hazards: &HazardLayer) {
+        for (snake, movement) in self.snakes.iter_mut().zip(moves) {
+            if !snake.alive {
+                continue;
+            }
+            let head = step(&snake.head(), movement);
+            snake.body.push_front(head);
+            snake.body.pop_back();
+            let damage = if self.food.contains(&head) {
+                1
+            } else {
+                1 + hazards.damage(&head)
+            };
+            snake.health = snake.health.saturating_sub(damage);
+        }
+
+        let mut eaten = vec![];
+        for snake in self.snakes.iter_mut().filter(|x| x.alive) {
+            let head = snake.head();
+            if self.food.contains(&head) {
+                snake.health = 100;
+                if let Some(tail) = snake.body.back().copied() {
+                    snake.body.push_back(tail);
+                }
+                eaten.push(head);
+            }
+        }
+        self.food.retain(|x| !eaten.contains(x));
+
+        let eliminated: Vec<bool> = (0..self.snakes.len()).map(|i| self.eliminated(i)).collect();
+        for (snake, eliminated) in self.snakes.iter_mut().zip(eliminated) {
+            if eliminated {
+                snake.alive = false;
+            }
+        }
+    }
+
+    fn eliminated(&self, index: usize) -> bool {
+        let snake = &self.snakes[index];
+        if !snake.alive {
+            return false;
+        }
+        let head = snake.head();
+        if snake.health == 0 || !self.contains(&head) {
+            return true;
+        }
+        self.snakes.iter().enumerate().any(|(i, other)| {
+            other.alive
+                && (other.body.iter().skip(1).any(|x| x == &head)
+                    || (i != index && other.head() == head && other.body.len() >= snake.body.len()))
+        })
+    }
+}
+
+// How the engine adds food at the end of every turn: enough to make up `minimum` if there's less
+// than that, otherwise one more with a `chance` percent chance. New food goes on a random empty
+// square, and on maps with walls never inside one.
+#[derive(Clone, Debug)]
+pub struct FoodSpawning {
+    pub chance: u32,
+    pub minimum: u32,
+    pub walls: Arc<HashSet<Coord>>,
+}
+
+impl FoodSpawning {
+    pub fn new(settings: &RulesetSettings, walls: &HashSet<Coord>) -> FoodSpawning {
+        FoodSpawning {
+            chance: settings.food_spawn_chance.min(100),
+            minimum: settings.minimum_food,
+            walls: Arc::new(walls.clone()),
+        }
+    }
+
+    // The ways food could spawn into `state`, as the probability of each and the food it adds,
+    // with not spawning any first whenever that can happen. There are far too many squares to try
+    // them all, so `samples` placements stand in for all of them, picked by `seed` so the same
+    // position always gets the same ones.
+    pub fn outcomes(&self, state: &SimState, samples: usize, seed: u64) -> Vec<(f64, Vec<Coord>)> {
+        let empty: Vec<Coord> = state
+            .empty_squares()
+            .into_iter()
+            .filter(|x| !self.walls.contains(x))
+            .collect();
+        let forced = (self.minimum as usize).saturating_sub(state.food.len());
+        let (spawned, spawn_probability) = if forced > 0 {
+            (forced.min(empty.len()), 1.0)
+        } else {
+            (1.min(empty.len()), self.chance as f64 / 100.0)
+        };
+        if spawned == 0 || spawn_probability == 0.0 || samples == 0 {
+            return vec![(1.0, vec![])];
+        }
+
+        let mut outcomes = vec![];
+        if spawn_probability < 1.0 {
+            outcomes.push((1.0 - spawn_probability, vec![]));
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..samples {
+            let food = sample(&mut rng, empty.len(), spawned)
+                .into_iter()
+                .map(|i| empty[i])
+                .collect();
+            outcomes.push((spawn_probability / samples as f64, food));
+        }
+        outcomes
+    }
+}
+
+fn step(coord: &Coord, movement: &Movement) -> Coord {
+    match movement {
+        Movement::Up => Coord {
+            x: coord.x,
+            y: coord.y + 1,
+        },
+        Movement::Down => Coord {
+            x: coord.x,
+            y: coord.y - 1,
+        },
+        Movement::Left => Coord {
+            x: coord.x - 1,
+            y: coord.y,
+        },
+        Movement::Right => Coord {
+            x: coord.x + 1,
+            y: coord.y,
+        },
+    }
+}
+
+#[test]
+fn advance_applies_standard_rules() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 5,
+            "width": 5,
+            "food": [{"x": 1, "y": 2}],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 3, "y": 3}, {"x": 4, "y": 3}, {"x": 4, "y": 2}, {"x": 4, "y": 1}],
+                "latency": "111",
+                "head": {"x": 3, "y": 3},
+                "length": 4
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 1}, {"x": 1, "y": 0}, {"x": 0, "y": 0}],
+            "latency": "111",
+            "head": {"x": 1, "y": 1},
+            "length": 3
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let hazards = HazardLayer::new(&state.board, 14);
+    let mut sim = SimState::new(&state.board, &state.you);
+    assert_eq!(
+        sim.moves(0),
+        vec![Movement::Up, Movement::Left, Movement::Right]
+    );
+
+    // We eat and grow, they just move
+    sim.advance(&[Movement::Up, Movement::Down], &hazards);
+    assert_eq!(sim.snakes[0].health, 100);
+    assert_eq!(sim.snakes[0].body.len(), 4);
+    assert_eq!(sim.snakes[1].health, 53);
+    assert!(sim.food.is_empty());
+
+    // Meeting head to head at equal length takes out both of us
+    sim.advance(&[Movement::Right, Movement::Left], &hazards);
+    assert!(!sim.snakes[0].alive);
+    assert!(!sim.snakes[1].alive);
+    assert!(sim.is_over());
+}
+
+#[test]
+fn food_spawns_to_minimum_or_by_chance() {
+    let board: Board = serde_json::from_str(
+        r#"{
+          "height": 3,
+          "width": 3,
+          "food": [],
+          "hazards": [],
+          "snakes": [
+            {
+              "id": "my-snake",
+              "name": "My Snake",
+              "health": 54,
+              "body": [{"x": 0, "y": 0}, {"x": 1, "y": 0}, {"x": 2, "y": 0}],
+              "latency": "111",
+              "head": {"x": 0, "y": 0},
+              "length": 3
+            }
+          ]
+        }"#,
+    )
+    .expect("failed to parse board");
+    let mut sim = SimState::new(&board, &board.snakes[0]);
+    assert_eq!(sim.empty_squares().len(), 6);
+
+    // Below the minimum, food always spawns
+    let mut spawning = FoodSpawning {
+        chance: 15,
+        minimum: 2,
+        walls: Arc::new(HashSet::new()),
+    };
+    let outcomes = spawning.outcomes(&sim, 2, 42);
+    assert_eq!(outcomes.len(), 2);
+    for (probability, food) in &outcomes {
+        assert_eq!(*probability, 0.5);
+        assert_eq!(food.len(), 2);
+        assert!(food.iter().all(|x| x.y > 0));
+    }
+    assert_eq!(spawning.outcomes(&sim, 2, 42), outcomes);
+
+    // Otherwise it's down to chance
+    sim.food = vec![Coord { x: 0, y: 2 }, Coord { x: 1, y: 2 }];
+    let outcomes = spawning.outcomes(&sim, 1, 42);
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0], (0.85, vec![]));
+    assert_eq!(outcomes[1].1.len(), 1);
+
+    // Nor does it spawn inside walls, which leaves one square on this board
+    spawning.walls = Arc::new(
+        [
+            Coord { x: 0, y: 1 },
+            Coord { x: 1, y: 1 },
+            Coord { x: 2, y: 2 },
+        ]
+        .iter()
+        .copied()
+        .collect(),
+    );
+    let outcomes = spawning.outcomes(&sim, 2, 42);
+    assert!(outcomes[1..]
+        .iter()
+        .all(|(_, food)| food == &[Coord { x: 2, y: 1 }]));
+}
@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    hazard::HazardLayer,
+    heuristic::{
+        AvoidBounds, AvoidHazards, AvoidSmallSpaces, AvoidSnakeBodies, AvoidWalls, Context,
+        HandleOpponentHeads, ScanFood,
+    },
+    parallel::{parallel_deepen, pool_size},
+    pipeline::Pipeline,
+    request::{Battlesnake, Board, Game},
+    response::{InfoResponse, MoveResponse},
+    search::{format_line, heuristic_order, searched_depth, MAX_DEPTH},
+    simulation::{FoodSpawning, SimState},
+    transposition::{self, keep_table, take_table},
+};
+
+#[cfg(test)]
+use crate::rocket;
+#[cfg(test)]
+use rocket::{
+    http::{ContentType, Status},
+    local::blocking::Client,
+};
+
+// Time left over from `Game.timeout` for the response to get back to the engine.
+const LATENCY_MARGIN_MS: u32 = 150;
+const TABLE_SIZE: usize = 1 << 16;
+
+pub fn info() -> InfoResponse {
+    info!("INFO");
+    InfoResponse {
+        api_version: "1".to_string(),
+        author: "Zachatoo".to_string(),
+        color: "#7a3cff".to_string(),
+        head: "smart-caterpillar".to_string(),
+        tail: "round-bum".to_string(),
+    }
+}
+
+pub fn start(game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
+    info!("{} GAME START", game.id);
+}
+
+pub fn end(game: &Game, _turn: &u32, _board: &Board, you: &Battlesnake) {
+    info!("{} GAME OVER", game.id);
+    transposition::forget(&game.id, &you.id);
+}
+
+// Only used to decide which moves the search tries first, so it's cheap rather than thorough.
+pub fn pipeline() -> Pipeline {
+    Pipeline::new()
+        .add(AvoidBounds)
+        .add(AvoidWalls)
+        .add(AvoidSnakeBodies)
+        .add(ScanFood)
+        .add(AvoidSmallSpaces)
+        .add(HandleOpponentHeads)
+        .add(AvoidHazards)
+        .configure("brainy")
+}
+
+// `received` is when the request came in, so time spent waiting for a thread to run on counts
+// against `Game.timeout` too.
+pub fn get_move(
+    game: &Game,
+    turn: &u32,
+    board: &Board,
+    you: &Battlesnake,
+    received: Instant,
+) -> MoveResponse {
+    let deadline =
+        received + Duration::from_millis(game.timeout.saturating_sub(LATENCY_MARGIN_MS) as u64);
+    let context = Context::new(game, turn, board, you);
+    let seeds = heuristic_order(&pipeline().evaluate(&context));
+
+    let hazards = HazardLayer::new(board, game.ruleset.settings.hazard_damage_per_turn);
+    let spawning = FoodSpawning::new(&game.ruleset.settings, &context.walls);
+    let state = SimState::new(board, you);
+
+    // Last turn's search most likely looked at where we are now, so pick up where it left off
+    let mut table = take_table(&game.id, &you.id, TABLE_SIZE);
+    match searched_depth(&mut table, &state, &hazards) {
+        Some(depth) => info!("Reusing last turn's search, {} turns deep here", depth),
+        None => info!("Nothing to reuse from last turn's search"),
+    }
+
+    let result = parallel_deepen(
+        &state, &hazards, spawning, &seeds, deadline, MAX_DEPTH, &mut table,
+    );
+    keep_table(&game.id, &you.id, table);
+
+    let movement = match result {
+        Some(result) => {
+            info!(
+                "Searched {} nodes to depth {} on {} threads, score {}, line {}",
+                result.nodes,
+                result.depth,
+                pool_size(),
+                result.score,
+                format_line(&result.pv)
+            );
+            result.movement
+        }
+        None => {
+            warn!("Ran out of time before searching a single turn");
+            seeds[0]
+        }
+    };
+
+    let chosen_move = movement.as_str().to_string();
+    info!("{} MOVE {}: {}", game.id, turn, chosen_move);
+    MoveResponse { chosen_move }
+}
+
+#[cfg(test)]
+static MOVE_URI: &str = "/brainy/move?x-api-key=valid_api_key";
+
+#[test]
+fn movement_avoid_longer_head() {
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(MOVE_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {"id": "game-id-string", "ruleset": {"name": "standard"}, "timeout": 500},
+                "turn": 10,
+                "board": {
+                  "height": 7,
+                  "width": 7,
+                  "food": [],
+                  "hazards": [],
+                  "snakes": [
+                    {
+                      "id": "snake-508e96ac-94ad-11ea-bb37",
+                      "name": "My Snake",
+                      "health": 54,
+                      "body": [{"x": 0, "y": 3}, {"x": 0, "y": 2}, {"x": 0, "y": 1}],
+                      "latency": "111",
+                      "head": {"x": 0, "y": 3},
+                      "length": 3
+                    },
+                    {
+                      "id": "snake-b67f4906-94ae-11ea-bb37",
+                      "name": "Another Snake",
+                      "health": 54,
+                      "body": [{"x": 0, "y": 5}, {"x": 1, "y": 5}, {"x": 2, "y": 5}, {"x": 3, "y": 5}],
+                      "latency": "111",
+                      "head": {"x": 0, "y": 5},
+                      "length": 4
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "snake-508e96ac-94ad-11ea-bb37",
+                  "name": "My Snake",
+                  "health": 54,
+                  "body": [{"x": 0, "y": 3}, {"x": 0, "y": 2}, {"x": 0, "y": 1}],
+                  "latency": "111",
+                  "head": {"x": 0, "y": 3},
+                  "length": 3
+                }
+              }"#,
+        )
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    // Up could meet the longer snake head-to-head
+    assert_eq!(response.into_string(), Some(r#"{"move":"right"}"#.into()));
+}
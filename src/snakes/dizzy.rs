@@ -1,8 +1,8 @@
 use crate::{
-    logic::{avoid_bounds, avoid_snake_bodies, scan_tail},
-    movement_set::WeightedMovementSet,
+    heuristic::{AvoidBounds, AvoidSnakeBodies, AvoidWalls, Context, ScanTail},
+    pipeline::Pipeline,
     request::{Battlesnake, Board, Game},
-    response::{InfoResponse, MoveShoutResponse},
+    response::{ExplainResponse, InfoResponse, MoveShoutResponse},
 };
 
 #[cfg(test)]
@@ -32,25 +32,23 @@ pub fn end(game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
     info!("{} GAME OVER", game.id);
 }
 
-pub fn get_move(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> MoveShoutResponse {
-    let mut movement_set = WeightedMovementSet::new();
+pub fn pipeline() -> Pipeline {
+    Pipeline::new()
+        .add(AvoidBounds)
+        .add(AvoidWalls)
+        .add(AvoidSnakeBodies)
+        .add(ScanTail)
+        .configure("dizzy")
+}
 
-    avoid_bounds(board.width, board.height, you, &mut movement_set);
-    avoid_snake_bodies(&board.snakes, you, &mut movement_set);
-    scan_tail(&board, you, &mut movement_set);
+pub fn get_move(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> MoveShoutResponse {
+    pipeline().get_move(&Context::new(game, turn, board, you))
+}
 
-    info!("Safe moves: {:?}", movement_set.moves);
-    let chosen_move = movement_set.pick_movement().as_str().to_string();
-    info!("{} MOVE {}: {}", game.id, turn, chosen_move);
-    MoveShoutResponse {
-        chosen_move,
-        shout: movement_set
-            .moves
-            .into_iter()
-            .map(|x| x.movement.as_str().to_owned())
-            .collect::<Vec<String>>()
-            .join(","),
-    }
+pub fn explain(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> ExplainResponse {
+    let mut context = Context::new(game, turn, board, you);
+    context.explaining = true;
+    pipeline().explain(&context)
 }
 
 #[cfg(test)]
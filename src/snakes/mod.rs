@@ -1,3 +1,4 @@
+pub mod brainy;
 pub mod dizzy;
 pub mod righty;
 pub mod rusty;
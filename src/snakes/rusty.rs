@@ -1,11 +1,13 @@
 use crate::{
-    logic::{
-        avoid_bounds, avoid_hazards, avoid_small_spaces, avoid_snake_bodies, handle_opponent_heads,
-        scan_food,
+    heuristic::{
+        AvoidBounds, AvoidChokepoints, AvoidHazards, AvoidSmallSpaces, AvoidSnakeBodies,
+        AvoidStarvation, AvoidWalls, Context, FillSpace, FollowCycle, HandleOpponentHeads,
+        ScanFood, TrapOpponents,
     },
-    movement_set::WeightedMovementSet,
+    pipeline::Pipeline,
     request::{Battlesnake, Board, Game},
-    response::{InfoResponse, MoveShoutResponse},
+    response::{ExplainResponse, InfoResponse, MoveShoutResponse},
+    survival,
 };
 
 #[cfg(test)]
@@ -31,36 +33,55 @@ pub fn start(game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
     info!("{} GAME START", game.id);
 }
 
-pub fn end(game: &Game, _turn: &u32, _board: &Board, _you: &Battlesnake) {
+pub fn end(game: &Game, _turn: &u32, _board: &Board, you: &Battlesnake) {
     info!("{} GAME OVER", game.id);
+    survival::forget(&game.id, &you.id);
 }
 
-pub fn get_move(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> MoveShoutResponse {
-    let mut movement_set = WeightedMovementSet::new();
+pub fn pipeline(context: &Context) -> Pipeline {
+    // Solo games are only lost to ourselves, so follow a cycle round the board when there is one
+    if context.cycle.is_some() {
+        return Pipeline::new()
+            .add(AvoidBounds)
+            .add(AvoidWalls)
+            .add(AvoidSnakeBodies)
+            .add(AvoidStarvation)
+            .add(AvoidSmallSpaces)
+            .add(FollowCycle)
+            .add(AvoidHazards)
+            .configure("rusty");
+    }
 
-    avoid_bounds(board.width, board.height, you, &mut movement_set);
-    avoid_snake_bodies(&board.snakes, you, &mut movement_set);
-    scan_food(&board, you, &mut movement_set);
-    avoid_small_spaces(&board, you, &mut movement_set);
-    handle_opponent_heads(&board.snakes, you, &mut movement_set);
-    avoid_hazards(&board.hazards, you, &mut movement_set);
+    Pipeline::new()
+        .add(AvoidBounds)
+        .add(AvoidWalls)
+        .add(AvoidSnakeBodies)
+        .add(ScanFood)
+        .add(AvoidStarvation)
+        .add(AvoidSmallSpaces)
+        .add(AvoidChokepoints)
+        .add(FillSpace)
+        .add(HandleOpponentHeads)
+        .add(TrapOpponents)
+        .add(AvoidHazards)
+        .configure("rusty")
+}
 
-    info!("Safe moves: {:?}", movement_set.moves);
-    let chosen_move = movement_set.pick_movement().as_str().to_string();
-    info!("{} MOVE {}: {}", game.id, turn, chosen_move);
-    MoveShoutResponse {
-        chosen_move,
-        shout: movement_set
-            .moves
-            .into_iter()
-            .map(|x| x.movement.as_str().to_owned())
-            .collect::<Vec<String>>()
-            .join(","),
-    }
+pub fn get_move(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> MoveShoutResponse {
+    let context = Context::new(game, turn, board, you);
+    pipeline(&context).get_move(&context)
+}
+
+pub fn explain(game: &Game, turn: &u32, board: &Board, you: &Battlesnake) -> ExplainResponse {
+    let mut context = Context::new(game, turn, board, you);
+    context.explaining = true;
+    pipeline(&context).explain(&context)
 }
 
 #[cfg(test)]
 static MOVE_URI: &str = "/rusty/move?x-api-key=valid_api_key";
+#[cfg(test)]
+static EXPLAIN_URI: &str = "/rusty/explain?x-api-key=valid_api_key";
 
 #[test]
 fn movement_avoid_moving_out_of_bounds() {
@@ -123,6 +144,212 @@ fn movement_avoid_moving_out_of_bounds() {
     assert_eq!(parsed_body.shout, "up");
 }
 
+#[test]
+fn movement_contest_food_when_starving() {
+    // The opponent would win the race to the nearby food, but the other food is too far away to
+    // reach before we starve.
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(MOVE_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {
+                  "id": "unique-game-id",
+                  "ruleset": {
+                    "name": "standard"
+                  },
+                  "timeout": 500
+                },
+                "turn": 0,
+                "board": {
+                  "height": 11,
+                  "width": 11,
+                  "food": [
+                    {"x": 2, "y": 5},
+                    {"x": 10, "y": 10}
+                  ],
+                  "hazards": [],
+                  "snakes": [
+                    {
+                      "id": "my-snake",
+                      "name": "My Snake",
+                      "health": 3,
+                      "body": [
+                        {"x": 5, "y": 5},
+                        {"x": 5, "y": 4},
+                        {"x": 5, "y": 3}
+                      ],
+                      "latency": "111",
+                      "head": {"x": 5, "y": 5},
+                      "length": 3
+                    },
+                    {
+                      "id": "other-snake",
+                      "name": "Other Snake",
+                      "health": 90,
+                      "body": [
+                        {"x": 0, "y": 5},
+                        {"x": 0, "y": 4},
+                        {"x": 0, "y": 3}
+                      ],
+                      "latency": "111",
+                      "head": {"x": 0, "y": 5},
+                      "length": 3
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "my-snake",
+                  "name": "My Snake",
+                  "health": 3,
+                  "body": [
+                    {"x": 5, "y": 5},
+                    {"x": 5, "y": 4},
+                    {"x": 5, "y": 3}
+                  ],
+                  "latency": "111",
+                  "head": {"x": 5, "y": 5},
+                  "length": 3
+                }
+              }"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let parsed_body = response
+        .into_json::<MoveShoutResponse>()
+        .expect("failed to parse response");
+    assert_eq!(parsed_body.chosen_move, "left");
+}
+
+#[test]
+fn movement_solo_follows_cycle_instead_of_food() {
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(MOVE_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {
+                  "id": "unique-game-id",
+                  "ruleset": {
+                    "name": "solo"
+                  },
+                  "timeout": 500
+                },
+                "turn": 0,
+                "board": {
+                  "height": 11,
+                  "width": 11,
+                  "food": [{"x": 2, "y": 2}],
+                  "hazards": [],
+                  "snakes": [
+                    {
+                      "id": "my-snake",
+                      "name": "My Snake",
+                      "health": 90,
+                      "body": [
+                        {"x": 2, "y": 1},
+                        {"x": 2, "y": 0},
+                        {"x": 1, "y": 0}
+                      ],
+                      "latency": "111",
+                      "head": {"x": 2, "y": 1},
+                      "length": 3
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "my-snake",
+                  "name": "My Snake",
+                  "health": 90,
+                  "body": [
+                    {"x": 2, "y": 1},
+                    {"x": 2, "y": 0},
+                    {"x": 1, "y": 0}
+                  ],
+                  "latency": "111",
+                  "head": {"x": 2, "y": 1},
+                  "length": 3
+                }
+              }"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let parsed_body = response
+        .into_json::<MoveShoutResponse>()
+        .expect("failed to parse response");
+    assert_eq!(parsed_body.chosen_move, "right");
+}
+
+#[test]
+fn movement_maze_hazards_are_walls() {
+    // Hazard on every side but down, where the corridor carries on
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(MOVE_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {
+                  "id": "unique-game-id",
+                  "ruleset": {
+                    "name": "standard",
+                    "settings": {"hazardDamagePerTurn": 14}
+                  },
+                  "map": "arcade_maze",
+                  "timeout": 500
+                },
+                "turn": 0,
+                "board": {
+                  "height": 11,
+                  "width": 11,
+                  "food": [{"x": 5, "y": 6}],
+                  "hazards": [
+                    {"x": 4, "y": 5},
+                    {"x": 6, "y": 5},
+                    {"x": 5, "y": 6}
+                  ],
+                  "snakes": [
+                    {
+                      "id": "my-snake",
+                      "name": "My Snake",
+                      "health": 90,
+                      "body": [
+                        {"x": 5, "y": 5},
+                        {"x": 5, "y": 5},
+                        {"x": 5, "y": 5}
+                      ],
+                      "latency": "111",
+                      "head": {"x": 5, "y": 5},
+                      "length": 3
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "my-snake",
+                  "name": "My Snake",
+                  "health": 90,
+                  "body": [
+                    {"x": 5, "y": 5},
+                    {"x": 5, "y": 5},
+                    {"x": 5, "y": 5}
+                  ],
+                  "latency": "111",
+                  "head": {"x": 5, "y": 5},
+                  "length": 3
+                }
+              }"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let parsed_body = response
+        .into_json::<MoveShoutResponse>()
+        .expect("failed to parse response");
+    assert_eq!(parsed_body.chosen_move, "down");
+    assert_eq!(parsed_body.shout, "down");
+}
+
 #[test]
 fn movement_tail_is_safe() {
     let client = Client::untracked(rocket()).expect("Failed to create client instance");
@@ -905,7 +1132,6 @@ fn movement_prefer_food_prefer_single_closer_food_to_two_further_food() {
     assert!(!parsed_body.shout.contains("down"));
 }
 
-#[ignore = "Not implemented yet"]
 #[test]
 fn movement_trap_enemy_snake_against_wall() {
     let client = Client::untracked(rocket()).expect("Failed to create client instance");
@@ -1206,7 +1432,6 @@ fn movement_avoid_small_spaces_prefer_potential_head_collision_to_self_trap() {
     assert!(!parsed_body.shout.contains("down"));
 }
 
-#[ignore = "Not implemented yet"]
 #[test]
 fn movement_avoid_small_spaces_between_self_and_enemy_snake() {
     let client = Client::untracked(rocket()).expect("Failed to create client instance");
@@ -1321,3 +1546,131 @@ fn movement_avoid_small_spaces_between_self_and_enemy_snake() {
     assert!(!parsed_body.shout.contains("up"));
     assert!(!parsed_body.shout.contains("down"));
 }
+
+#[test]
+fn movement_reject_snake_without_body() {
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(MOVE_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {
+                  "id": "unique-game-id",
+                  "ruleset": {
+                    "name": "standard"
+                  },
+                  "timeout": 500
+                },
+                "turn": 0,
+                "board": {
+                  "height": 11,
+                  "width": 11,
+                  "food": [],
+                  "hazards": [],
+                  "snakes": [
+                    {
+                      "id": "my-snake",
+                      "name": "My Snake",
+                      "health": 54,
+                      "body": [],
+                      "latency": "111",
+                      "head": {"x": 0, "y": 0},
+                      "length": 0
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "my-snake",
+                  "name": "My Snake",
+                  "health": 54,
+                  "body": [],
+                  "latency": "111",
+                  "head": {"x": 0, "y": 0},
+                  "length": 0
+                }
+              }"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let body = response.into_string().expect("failed to read response");
+    assert!(body.contains("empty body"));
+}
+
+#[test]
+fn explain_reports_removals_and_pick() {
+    let client = Client::untracked(rocket()).expect("Failed to create client instance");
+    let response = client
+        .post(EXPLAIN_URI)
+        .header(ContentType::JSON)
+        .body(
+            r#"{
+                "game": {
+                  "id": "unique-game-id",
+                  "ruleset": {
+                    "name": "standard"
+                  },
+                  "timeout": 500
+                },
+                "turn": 0,
+                "board": {
+                  "height": 11,
+                  "width": 11,
+                  "food": [{"x": 0, "y": 3}],
+                  "hazards": [],
+                  "snakes": [
+                    {
+                      "id": "my-snake",
+                      "name": "My Snake",
+                      "health": 54,
+                      "body": [
+                        {"x": 0, "y": 0},
+                        {"x": 1, "y": 0},
+                        {"x": 2, "y": 0}
+                      ],
+                      "latency": "111",
+                      "head": {"x": 0, "y": 0},
+                      "length": 3
+                    }
+                  ]
+                },
+                "you": {
+                  "id": "my-snake",
+                  "name": "My Snake",
+                  "health": 54,
+                  "body": [
+                    {"x": 0, "y": 0},
+                    {"x": 1, "y": 0},
+                    {"x": 2, "y": 0}
+                  ],
+                  "latency": "111",
+                  "head": {"x": 0, "y": 0},
+                  "length": 3
+                }
+              }"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let parsed_body = response
+        .into_json::<ExplainResponse>()
+        .expect("failed to parse response");
+    assert_eq!(parsed_body.chosen_move, "up");
+    let removed_by = |movement: &str| {
+        parsed_body
+            .moves
+            .iter()
+            .find(|x| x.movement == movement)
+            .and_then(|x| x.removed_by.to_owned())
+    };
+    assert_eq!(removed_by("up"), None);
+    assert_eq!(removed_by("down").as_deref(), Some("avoid_bounds"));
+    assert_eq!(removed_by("left").as_deref(), Some("avoid_bounds"));
+    assert_eq!(removed_by("right").as_deref(), Some("avoid_snake_bodies"));
+    let up = &parsed_body.moves[0];
+    assert_eq!(up.score_deltas[0].source, "scan_food");
+    assert_eq!(up.score_deltas[0].delta, 30);
+    assert_eq!(
+        up.score_deltas[0].reason,
+        "toward uncontested food at (0, 3)"
+    );
+}
@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::get_adjacent_nodes,
+    logic::snake_is_stacked,
+    movement_set::Movement,
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::{heuristic::Context, request::GameState};
+
+// Health to spare on the way round the cycle to the next food before we start cutting corners.
+const HUNGER_MARGIN: usize = 10;
+// Squares kept between our head and tail when cutting corners, in case we eat on the way.
+const SHORTCUT_BUFFER: usize = 2;
+
+// A route through every square of the board that ends where it started. Following it can never
+// trap us however long we get, since our body only ever trails behind our head along it.
+pub struct HamiltonianCycle {
+    width: u32,
+    // The position along the cycle of each square, if it's on the cycle at all
+    positions: Vec<Option<usize>>,
+    length: usize,
+}
+
+impl HamiltonianCycle {
+    // We go out along the bottom row, zigzag back over the rest of the board leaving the first
+    // column free, then return down the first column. That needs an even number of rows, or
+    // columns if we turn the board on its side. When both are odd there's no cycle through every
+    // square, so we leave out the bottom left corner and weave through the bottom two rows
+    // instead. Maps with walls usually have no cycle round them at all, so if this one runs into
+    // a wall there's no cycle.
+    pub fn new(width: u32, height: u32, walls: &HashSet<Coord>) -> Option<HamiltonianCycle> {
+        if width < 2 || height < 2 {
+            return None;
+        }
+        let transpose = !height.is_multiple_of(2) && width.is_multiple_of(2);
+        let (columns, rows) = if transpose {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let mut route = vec![];
+        if rows.is_multiple_of(2) {
+            for x in 0..columns {
+                route.push((x, 0));
+            }
+        } else {
+            route.push((0, 1));
+            for x in (1..columns).step_by(2) {
+                route.extend([(x, 1), (x, 0), (x + 1, 0), (x + 1, 1)]);
+            }
+        }
+        let first_row = if rows.is_multiple_of(2) { 1 } else { 2 };
+        for y in first_row..rows {
+            if (y - first_row).is_multiple_of(2) {
+                for x in (1..columns).rev() {
+                    route.push((x, y));
+                }
+            } else {
+                for x in 1..columns {
+                    route.push((x, y));
+                }
+            }
+        }
+        for y in (first_row..rows).rev() {
+            route.push((0, y));
+        }
+
+        let mut cycle = HamiltonianCycle {
+            width,
+            positions: vec![None; (width * height) as usize],
+            length: route.len(),
+        };
+        for (position, (x, y)) in route.into_iter().enumerate() {
+            let (x, y) = if transpose { (y, x) } else { (x, y) };
+            let coord = Coord {
+                x: x as i32,
+                y: y as i32,
+            };
+            if walls.contains(&coord) {
+                return None;
+            }
+            let index = cycle.index(&coord);
+            cycle.positions[index] = Some(position);
+        }
+        Some(cycle)
+    }
+
+    fn index(&self, coord: &Coord) -> usize {
+        (coord.y * self.width as i32 + coord.x) as usize
+    }
+
+    // How many steps along the cycle it is from `from` to `to`, if they're both on it.
+    pub fn distance(&self, from: &Coord, to: &Coord) -> Option<usize> {
+        let from = self.positions[self.index(from)]?;
+        let to = self.positions[self.index(to)]?;
+        Some((to + self.length - from) % self.length)
+    }
+}
+
+// The move to make in a game with no opponents: follow the cycle, and only cut across it towards
+// food when we'd otherwise starve going the long way round. None if we've somehow come off the
+// cycle.
+pub fn solo_move(
+    board: &Board,
+    you: &Battlesnake,
+    cycle: &HamiltonianCycle,
+) -> Option<(Movement, String)> {
+    let head = you.head;
+    let tail = *you.body.last()?;
+    let body = if snake_is_stacked(you) {
+        &you.body[..]
+    } else {
+        &you.body[..you.body.len() - 1]
+    };
+    let moves: Vec<_> = get_adjacent_nodes(&head)
+        .into_iter()
+        .filter(|x| board.contains(&x.coord) && !body.contains(&x.coord))
+        .filter_map(|x| Some((cycle.distance(&head, &x.coord)?, x)))
+        .collect();
+
+    let nearest_food = board
+        .food
+        .iter()
+        .filter_map(|x| Some((cycle.distance(&head, x)?, x)))
+        .min_by_key(|x| x.0);
+    if let (Some((food_distance, food)), Some(tail_distance)) =
+        (nearest_food, cycle.distance(&head, &tail))
+    {
+        let hungry = (you.health as usize) <= food_distance + HUNGER_MARGIN;
+        let short = (you.length as usize) < cycle.length / 2;
+        if hungry && short {
+            // Jumping ahead along the cycle is safe as long as we land before our tail
+            let shortcut = moves
+                .iter()
+                .filter(|(distance, _)| distance + SHORTCUT_BUFFER < tail_distance)
+                .min_by_key(|(_, x)| cycle.distance(&x.coord, food));
+            if let Some((_, x)) = shortcut {
+                return Some((
+                    x.movement,
+                    format!("shortcut toward food at ({}, {})", food.x, food.y),
+                ));
+            }
+        }
+    }
+
+    moves
+        .iter()
+        .find(|(distance, _)| *distance == 1)
+        .map(|(_, x)| (x.movement, "next square on the cycle".to_string()))
+}
+
+#[test]
+fn cycle_visits_every_square_once() {
+    for (width, height) in [(4, 4), (7, 4), (4, 7), (11, 10), (3, 3), (7, 7), (11, 11)] {
+        let cycle =
+            HamiltonianCycle::new(width, height, &HashSet::new()).expect("should have a cycle");
+        let mut positions: Vec<usize> = cycle.positions.iter().flatten().copied().collect();
+        positions.sort();
+        assert_eq!(positions, (0..cycle.length).collect::<Vec<_>>());
+        // Only the corner is left out, and only when there's no way round it
+        let squares = (width * height) as usize;
+        assert_eq!(cycle.length, squares - squares % 2);
+
+        // Each square is next to the one after it, including the last back to the first
+        for x in 0..width as i32 {
+            for y in 0..height as i32 {
+                let coord = Coord { x, y };
+                if cycle.distance(&coord, &coord).is_none() {
+                    continue;
+                }
+                let next = get_adjacent_nodes(&coord)
+                    .into_iter()
+                    .filter(|node| {
+                        node.coord.x >= 0
+                            && node.coord.y >= 0
+                            && node.coord.x < width as i32
+                            && node.coord.y < height as i32
+                    })
+                    .filter(|node| cycle.distance(&coord, &node.coord) == Some(1))
+                    .count();
+                assert_eq!(next, 1);
+            }
+        }
+    }
+}
+
+#[test]
+fn maze_walls_leave_no_cycle() {
+    let mut state: GameState = serde_json::from_str(
+        r#"{
+          "game": {
+            "id": "unique-game-id",
+            "ruleset": {"name": "solo"},
+            "map": "arcade_maze",
+            "timeout": 500
+          },
+          "turn": 0,
+          "board": {
+            "height": 7,
+            "width": 7,
+            "food": [],
+            "hazards": [{"x": 3, "y": 3}, {"x": 3, "y": 4}],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 1, "y": 0}, {"x": 0, "y": 0}],
+            "latency": "111",
+            "head": {"x": 1, "y": 0},
+            "length": 2
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    // The cycle would run through the walls, so there's none to follow
+    let context = Context::new(&state.game, &state.turn, &state.board, &state.you);
+    assert!(context.cycle.is_none());
+    // Without the maze the hazards are only hazards
+    state.game.map = None;
+    let context = Context::new(&state.game, &state.turn, &state.board, &state.you);
+    assert!(context.cycle.is_some());
+    // A wall the cycle goes round anyway doesn't get in the way
+    let corner = [Coord { x: 0, y: 0 }].iter().copied().collect();
+    assert!(HamiltonianCycle::new(7, 7, &corner).is_some());
+}
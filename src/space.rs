@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+
+use crate::{
+    graph::{distance_map, Source},
+    request::{Battlesnake, Board, Coord},
+};
+
+// How many turns until each square of the board stops being part of a snake, 0 for squares
+// that are already free. A snake's body moves up one segment per turn, so the segment `i` from
+// its head is gone after `length - i` turns. Stacked segments after eating take the time of
+// the segment nearest the head, which keeps a stacked tail around for the extra turn.
+pub struct VacateTimes {
+    width: u32,
+    height: u32,
+    turns: Vec<usize>,
+}
+
+impl VacateTimes {
+    pub fn new(board: &Board) -> VacateTimes {
+        let mut times = VacateTimes::empty(board);
+        for snake in &board.snakes {
+            times.add_body(&snake.body, 0, 0);
+        }
+        times
+    }
+
+    // The board after `you` has moved along `path` and eaten at the end of it. We can't know
+    // where opponents went meanwhile, so their bodies stay put with their tails counting down.
+    pub fn after_eating(board: &Board, you: &Battlesnake, path: &[Coord]) -> VacateTimes {
+        let mut times = VacateTimes::empty(board);
+        for snake in board.snakes.iter().filter(|x| x.id != you.id) {
+            times.add_body(&snake.body, 0, path.len());
+        }
+        let mut body: Vec<Coord> = path.iter().rev().chain(you.body.iter()).copied().collect();
+        body.truncate(you.body.len());
+        if let Some(tail) = body.last().copied() {
+            body.push(tail);
+        }
+        times.add_body(&body, 0, 0);
+        times
+    }
+
+    // Marks `coord` as taken for the next `turns` turns, e.g. by the square our head moves into.
+    pub fn occupy(&mut self, coord: &Coord, turns: usize) {
+        if let Some(index) = self.index(coord) {
+            self.turns[index] = self.turns[index].max(turns);
+        }
+    }
+
+    // Marks squares that never free up, like hazards the map uses as walls.
+    pub fn add_walls<'a, I>(&mut self, walls: I)
+    where
+        I: IntoIterator<Item = &'a Coord>,
+    {
+        for wall in walls {
+            self.occupy(wall, usize::MAX);
+        }
+    }
+
+    fn empty(board: &Board) -> VacateTimes {
+        VacateTimes {
+            width: board.width,
+            height: board.height,
+            turns: vec![0; (board.width * board.height) as usize],
+        }
+    }
+
+    // Keeps `snake` on the board for `turns` longer than it would otherwise be, e.g. because it
+    // is about to eat and won't move its tail.
+    pub fn delay_snake(&mut self, snake: &Battlesnake, turns: usize) {
+        self.add_body(&snake.body, turns, 0);
+    }
+
+    // Adds a body that stays `delay` turns longer than usual, `elapsed` turns from now.
+    fn add_body(&mut self, body: &[Coord], delay: usize, elapsed: usize) {
+        let length = body.len();
+        for (i, coord) in body.iter().enumerate() {
+            if let Some(index) = self.index(coord) {
+                let turns = (length - i + delay).saturating_sub(elapsed);
+                self.turns[index] = self.turns[index].max(turns);
+            }
+        }
+    }
+
+    fn index(&self, coord: &Coord) -> Option<usize> {
+        if coord.x < 0
+            || coord.y < 0
+            || coord.x >= self.width as i32
+            || coord.y >= self.height as i32
+        {
+            return None;
+        }
+        Some((coord.y * self.width as i32 + coord.x) as usize)
+    }
+
+    pub fn turns(&self, coord: &Coord) -> usize {
+        self.index(coord).map_or(0, |i| self.turns[i])
+    }
+}
+
+// Counts the squares we could reach after moving to `start`, treating body segments as passable
+// from the turn they vacate. Squares are only checked at the earliest turn we could get to them,
+// so this doesn't account for killing time elsewhere while waiting for a square to open up.
+// Counting stops once `enough` squares have been found.
+pub fn reachable_area(board: &Board, times: &VacateTimes, start: &Coord, enough: usize) -> usize {
+    let mut area = 0;
+    distance_map(
+        board.width,
+        board.height,
+        &[Source {
+            coord: *start,
+            first_move: None,
+            cost: 0,
+        }],
+        // `start` is entered next turn, so a square `steps` moves away is entered on turn
+        // `steps + 1`.
+        |x, steps| times.turns(x) <= steps + 1,
+        |_, _| 1,
+        |_, _| {
+            area += 1;
+            area >= enough
+        },
+    );
+    area
+}
+
+// Counts the squares `snake` could reach from where its head is now, not counting the head.
+// Squares `steps` moves away are entered on turn `steps`, rather than a turn later as in
+// `reachable_area`.
+pub fn area_from_head(
+    board: &Board,
+    times: &VacateTimes,
+    snake: &Battlesnake,
+    enough: usize,
+) -> usize {
+    let mut area = 0;
+    distance_map(
+        board.width,
+        board.height,
+        &[Source {
+            coord: snake.head,
+            first_move: None,
+            cost: 0,
+        }],
+        |x, steps| times.turns(x) <= steps,
+        |_, _| 1,
+        |x, _| {
+            if x != &snake.head {
+                area += 1;
+            }
+            area >= enough
+        },
+    );
+    area
+}
+
+// Counts the squares we could reach from the food at the end of `path` after eating it, stopping
+// once `enough` are found. Our grown body and stacked tail are in the way, so food in a pocket
+// can look reachable and still leave us nowhere to go once we've eaten.
+pub fn space_after_eating(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    path: &[Coord],
+    enough: usize,
+) -> usize {
+    let food = match path.last() {
+        Some(x) => x,
+        None => return 0,
+    };
+    let mut times = VacateTimes::after_eating(board, you, path);
+    times.add_walls(walls);
+    let mut area = 0;
+    distance_map(
+        board.width,
+        board.height,
+        &[Source {
+            coord: *food,
+            first_move: None,
+            cost: 0,
+        }],
+        |x, steps| times.turns(x) <= steps,
+        |_, _| 1,
+        |_, _| {
+            area += 1;
+            area >= enough
+        },
+    );
+    area
+}
+
+#[test]
+fn vacate_times_count_down_from_head_to_tail() {
+    let board: Board = serde_json::from_str(
+        r#"{
+          "height": 5,
+          "width": 5,
+          "food": [],
+          "hazards": [],
+          "snakes": [
+            {
+              "id": "my-snake",
+              "name": "My Snake",
+              "health": 54,
+              "body": [{"x": 0, "y": 2}, {"x": 0, "y": 1}, {"x": 0, "y": 0}, {"x": 0, "y": 0}],
+              "latency": "111",
+              "head": {"x": 0, "y": 2},
+              "length": 4
+            }
+          ]
+        }"#,
+    )
+    .expect("failed to parse board");
+    let mut times = VacateTimes::new(&board);
+
+    assert_eq!(times.turns(&Coord { x: 0, y: 2 }), 4);
+    assert_eq!(times.turns(&Coord { x: 0, y: 1 }), 3);
+    // Stacked tail after eating
+    assert_eq!(times.turns(&Coord { x: 0, y: 0 }), 2);
+    assert_eq!(times.turns(&Coord { x: 1, y: 0 }), 0);
+
+    times.delay_snake(&board.snakes[0], 1);
+    assert_eq!(times.turns(&Coord { x: 0, y: 0 }), 3);
+}
+
+#[test]
+fn reachable_area_counts_squares_behind_vacating_tail() {
+    // A 3 wide board split by an opponent lying along the middle column, whose tail at the
+    // bottom will move out of the way in time for us to get past.
+    let board: Board = serde_json::from_str(
+        r#"{
+          "height": 4,
+          "width": 3,
+          "food": [],
+          "hazards": [],
+          "snakes": [
+            {
+              "id": "other-snake",
+              "name": "Other Snake",
+              "health": 54,
+              "body": [{"x": 1, "y": 3}, {"x": 1, "y": 2}, {"x": 1, "y": 1}, {"x": 1, "y": 0}],
+              "latency": "111",
+              "head": {"x": 1, "y": 3},
+              "length": 4
+            }
+          ]
+        }"#,
+    )
+    .expect("failed to parse board");
+    let mut times = VacateTimes::new(&board);
+
+    // From (0, 1) each segment of the middle column is gone by the time we reach it, which opens
+    // up the whole board.
+    let area = reachable_area(&board, &times, &Coord { x: 0, y: 1 }, usize::MAX);
+    assert_eq!(area, 12);
+
+    // If the opponent stays put for a few more turns we're stuck in the left hand column.
+    times.delay_snake(&board.snakes[0], 3);
+    let area = reachable_area(&board, &times, &Coord { x: 0, y: 1 }, usize::MAX);
+    assert_eq!(area, 4);
+}
+
+#[test]
+fn space_after_eating_accounts_for_growth() {
+    // An opponent walls off the left hand column, with food at the bottom and in the middle.
+    let board: Board = serde_json::from_str(
+        r#"{
+          "height": 5,
+          "width": 5,
+          "food": [{"x": 0, "y": 0}, {"x": 0, "y": 2}],
+          "hazards": [],
+          "snakes": [
+            {
+              "id": "my-snake",
+              "name": "My Snake",
+              "health": 54,
+              "body": [{"x": 1, "y": 0}, {"x": 2, "y": 0}, {"x": 3, "y": 0}],
+              "latency": "111",
+              "head": {"x": 1, "y": 0},
+              "length": 3
+            },
+            {
+              "id": "other-snake",
+              "name": "Other Snake",
+              "health": 54,
+              "body": [
+                {"x": 1, "y": 4}, {"x": 1, "y": 3}, {"x": 1, "y": 2}, {"x": 1, "y": 1},
+                {"x": 2, "y": 1}, {"x": 3, "y": 1}, {"x": 4, "y": 1}, {"x": 4, "y": 2}
+              ],
+              "latency": "111",
+              "head": {"x": 1, "y": 4},
+              "length": 8
+            }
+          ]
+        }"#,
+    )
+    .expect("failed to parse board");
+    let you = &board.snakes[0];
+
+    // Eating at the bottom leaves the rest of the column free.
+    let path = [Coord { x: 0, y: 0 }];
+    assert_eq!(
+        space_after_eating(&board, you, &HashSet::new(), &path, usize::MAX),
+        5
+    );
+
+    // Eating in the middle leaves our body blocking the way back out, with only two squares above.
+    let path = [
+        Coord { x: 0, y: 0 },
+        Coord { x: 0, y: 1 },
+        Coord { x: 0, y: 2 },
+    ];
+    assert_eq!(
+        space_after_eating(&board, you, &HashSet::new(), &path, usize::MAX),
+        3
+    );
+}
@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{
+    graph::{distance_map, get_adjacent_nodes, Source},
+    hazard::HazardLayer,
+    request::{Battlesnake, Board, Coord},
+    space::VacateTimes,
+};
+
+// Give up looking for a longer path after this many steps of the search.
+const SEARCH_BUDGET: usize = 20_000;
+// Games that never send `/end` would keep their plans forever, so only this many are kept.
+const KEPT_PLANS: usize = 32;
+
+// Plans are kept between turns so we don't change our mind about how to fill a region halfway
+// through filling it.
+struct Plan {
+    turn: u32,
+    path: VecDeque<Coord>,
+    last_used: Instant,
+}
+
+// Keyed by game ID and our snake's ID, since more than one of our snakes can be in a game.
+static PLANS: Mutex<BTreeMap<(String, String), Plan>> = Mutex::new(BTreeMap::new());
+
+// The free squares we can reach, if there are opponents left but none of their heads are next to
+// any of them. Bodies are treated as walls, so this is the region we're sealed into for now rather
+// than forever.
+pub fn isolated_region(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+) -> Option<Vec<Coord>> {
+    let blocked: HashSet<Coord> = board
+        .snakes
+        .iter()
+        .flat_map(|x| x.body.iter().copied())
+        .chain(walls.iter().copied())
+        .collect();
+    let map = distance_map(
+        board.width,
+        board.height,
+        &[Source {
+            coord: you.head,
+            first_move: None,
+            cost: 0,
+        }],
+        |x, _| !blocked.contains(x),
+        |_, _| 1,
+        |_, _| false,
+    );
+    let region: Vec<Coord> = map
+        .reached()
+        .iter()
+        .filter(|x| *x != &you.head)
+        .copied()
+        .collect();
+
+    let opponent_heads: Vec<Coord> = board
+        .snakes
+        .iter()
+        .filter(|x| x.id != you.id)
+        .map(|x| x.head)
+        .collect();
+    let contested = region.iter().any(|coord| {
+        get_adjacent_nodes(coord)
+            .iter()
+            .any(|x| opponent_heads.contains(&x.coord))
+    });
+    if region.is_empty() || opponent_heads.is_empty() || contested {
+        return None;
+    }
+    Some(region)
+}
+
+// Approximates the longest path through `region` and the squares our own body leaves behind,
+// which is as many turns as we can survive in it. Squares are tried in order of fewest onward
+// exits, which fills corners and dead ends before they get cut off.
+pub fn plan_fill(
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+    region: &[Coord],
+) -> Vec<Coord> {
+    let mut allowed: HashSet<Coord> = region.iter().copied().collect();
+    allowed.extend(you.body.iter().copied());
+    let food: HashSet<Coord> = board
+        .food
+        .iter()
+        .filter(|x| allowed.contains(x))
+        .copied()
+        .collect();
+    // Every allowed square but the one we're on, unless we'd starve first
+    let mut goal = allowed.len() - 1;
+    if food.is_empty() {
+        goal = goal.min(you.health.saturating_sub(1) as usize);
+    }
+    let mut times = VacateTimes::new(board);
+    times.add_walls(walls);
+    let mut fill = Fill {
+        times,
+        hazards,
+        food,
+        goal,
+        allowed,
+        visited: HashSet::new(),
+        path: vec![],
+        best: vec![],
+        budget: SEARCH_BUDGET,
+    };
+    fill.visited.insert(you.head);
+    fill.extend(you.head, you.health);
+    fill.best
+}
+
+struct Fill<'a> {
+    times: VacateTimes,
+    hazards: &'a HazardLayer,
+    food: HashSet<Coord>,
+    allowed: HashSet<Coord>,
+    goal: usize,
+    visited: HashSet<Coord>,
+    path: Vec<Coord>,
+    best: Vec<Coord>,
+    budget: usize,
+}
+
+impl Fill<'_> {
+    // Whether `coord` can be entered on turn `turn` from now.
+    fn open(&self, coord: &Coord, turn: usize) -> bool {
+        self.allowed.contains(coord)
+            && !self.visited.contains(coord)
+            && self.times.turns(coord) <= turn
+    }
+
+    fn extend(&mut self, from: Coord, health: u32) {
+        if self.path.len() > self.best.len() {
+            self.best = self.path.clone();
+        }
+        if self.best.len() >= self.goal || self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+
+        let turn = self.path.len() + 1;
+        let mut candidates: Vec<(usize, Coord)> = get_adjacent_nodes(&from)
+            .iter()
+            .filter(|x| self.open(&x.coord, turn))
+            .map(|x| {
+                let exits = get_adjacent_nodes(&x.coord)
+                    .iter()
+                    .filter(|y| self.open(&y.coord, turn + 1))
+                    .count();
+                (exits, x.coord)
+            })
+            .collect();
+        candidates.sort_by_key(|x| x.0);
+
+        for (_, coord) in candidates {
+            let health = if self.food.contains(&coord) {
+                100
+            } else {
+                health.saturating_sub(self.hazards.health_cost(&coord))
+            };
+            if health == 0 {
+                continue;
+            }
+            self.visited.insert(coord);
+            self.path.push(coord);
+            self.extend(coord, health);
+            self.path.pop();
+            self.visited.remove(&coord);
+            if self.best.len() >= self.goal || self.budget == 0 {
+                return;
+            }
+        }
+    }
+}
+
+// The next square to move to while we're sealed in a region with no opponents, along with how
+// many turns the plan lasts. The plan from the previous turn is reused as long as we followed it
+// and it's still open, and this turn's plan is kept for the next.
+pub fn next_square(
+    game_id: &str,
+    turn: u32,
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+) -> Option<(Coord, usize)> {
+    let path = match current_plan(game_id, turn, board, you, walls, hazards) {
+        Some(x) if !x.is_empty() => x,
+        _ => {
+            forget(game_id, &you.id);
+            return None;
+        }
+    };
+
+    let next = *path.front()?;
+    let planned = path.len();
+    let mut plans = PLANS.lock().unwrap_or_else(|x| x.into_inner());
+    plans.insert(
+        (game_id.to_string(), you.id.to_owned()),
+        Plan {
+            turn,
+            path,
+            last_used: Instant::now(),
+        },
+    );
+    while plans.len() > KEPT_PLANS {
+        let oldest = plans
+            .iter()
+            .min_by_key(|(_, x)| x.last_used)
+            .map(|(key, _)| key.to_owned());
+        match oldest {
+            Some(key) => plans.remove(&key),
+            None => break,
+        };
+    }
+    Some((next, planned))
+}
+
+// The same as `next_square`, but without keeping anything, for looking at a turn that isn't
+// being played.
+pub fn peek_square(
+    game_id: &str,
+    turn: u32,
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+) -> Option<(Coord, usize)> {
+    let path = current_plan(game_id, turn, board, you, walls, hazards)?;
+    Some((*path.front()?, path.len()))
+}
+
+// The kept plan if it still holds this turn, otherwise a new one. None if we aren't isolated.
+fn current_plan(
+    game_id: &str,
+    turn: u32,
+    board: &Board,
+    you: &Battlesnake,
+    walls: &HashSet<Coord>,
+    hazards: &HazardLayer,
+) -> Option<VecDeque<Coord>> {
+    let region = isolated_region(board, you, walls)?;
+
+    let mut times = VacateTimes::new(board);
+    times.add_walls(walls);
+    let cached = PLANS
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .get(&(game_id.to_string(), you.id.to_owned()))
+        .and_then(|plan| {
+            let mut path = plan.path.clone();
+            if plan.turn + 1 != turn || path.pop_front() != Some(you.head) {
+                return None;
+            }
+            let open = path
+                .iter()
+                .enumerate()
+                .all(|(i, x)| board.contains(x) && times.turns(x) <= i + 1);
+            if open && !path.is_empty() {
+                Some(path)
+            } else {
+                None
+            }
+        });
+    match cached {
+        Some(x) => {
+            debug!("Following survival plan, {} turns left", x.len());
+            Some(x)
+        }
+        None => {
+            let path = VecDeque::from(plan_fill(board, you, walls, hazards, &region));
+            info!(
+                "Isolated in region of {} squares, planned {} turns",
+                region.len(),
+                path.len()
+            );
+            Some(path)
+        }
+    }
+}
+
+pub fn forget(game_id: &str, snake_id: &str) {
+    PLANS
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .remove(&(game_id.to_string(), snake_id.to_string()));
+}
+
+#[cfg(test)]
+fn walled_in_board() -> Board {
+    // Our snake walls off a 2x2 room on the left of the board, the opponent is on the right.
+    serde_json::from_str(
+        r#"{
+          "height": 3,
+          "width": 7,
+          "food": [],
+          "hazards": [],
+          "snakes": [
+            {
+              "id": "my-snake",
+              "name": "My Snake",
+              "health": 54,
+              "body": [
+                {"x": 0, "y": 2}, {"x": 1, "y": 2}, {"x": 2, "y": 2}, {"x": 2, "y": 1},
+                {"x": 2, "y": 0}, {"x": 3, "y": 0}
+              ],
+              "latency": "111",
+              "head": {"x": 0, "y": 2},
+              "length": 6
+            },
+            {
+              "id": "other-snake",
+              "name": "Other Snake",
+              "health": 54,
+              "body": [{"x": 5, "y": 1}],
+              "latency": "111",
+              "head": {"x": 5, "y": 1},
+              "length": 1
+            }
+          ]
+        }"#,
+    )
+    .expect("failed to parse board")
+}
+
+#[test]
+fn plan_fill_covers_region_and_vacated_body() {
+    let board = walled_in_board();
+    let you = &board.snakes[0];
+    let region = isolated_region(&board, you, &HashSet::new()).expect("region should be isolated");
+    assert_eq!(region.len(), 4);
+
+    // Every square of the room, and then along our own body as it moves out of the way.
+    let path = plan_fill(
+        &board,
+        you,
+        &HashSet::new(),
+        &HazardLayer::new(&board, 14),
+        &region,
+    );
+    for coord in &region {
+        assert!(path.contains(coord));
+    }
+    assert_eq!(path.len(), 9);
+    assert_eq!(path[8], Coord { x: 3, y: 0 });
+}
+
+#[test]
+fn next_square_follows_cached_plan() {
+    let mut board = walled_in_board();
+    let hazards = HazardLayer::new(&board, 14);
+    let walls = HashSet::new();
+    let (first, planned) = next_square(
+        "cached-game",
+        10,
+        &board,
+        &board.snakes[0],
+        &walls,
+        &hazards,
+    )
+    .expect("should plan");
+    assert_eq!(planned, 9);
+    // Whatever our other snake in the game is up to doesn't touch this plan
+    let _ = next_square(
+        "cached-game",
+        10,
+        &board,
+        &board.snakes[1],
+        &walls,
+        &hazards,
+    );
+    // Nor does explaining an earlier turn
+    let peeked = peek_square("cached-game", 5, &board, &board.snakes[0], &walls, &hazards);
+    assert_eq!(peeked, Some((first, planned)));
+
+    // Move along the plan, and the rest of it is picked up next turn.
+    let you = &mut board.snakes[0];
+    you.body.insert(0, first);
+    you.body.pop();
+    you.head = first;
+    let (_, planned) = next_square(
+        "cached-game",
+        11,
+        &board,
+        &board.snakes[0],
+        &walls,
+        &hazards,
+    )
+    .expect("should follow plan");
+    assert_eq!(planned, 8);
+
+    // Once an opponent can get in there's no plan.
+    board.snakes[1].head = Coord { x: 1, y: 1 };
+    board.snakes[1].body = vec![Coord { x: 1, y: 1 }];
+    assert_eq!(
+        next_square(
+            "cached-game",
+            12,
+            &board,
+            &board.snakes[0],
+            &walls,
+            &hazards
+        ),
+        None
+    );
+}
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    graph::get_adjacent_nodes,
+    request::{Battlesnake, Board, Coord},
+};
+
+#[cfg(test)]
+use crate::request::GameState;
+
+// Where an opponent's head could be two turns from now, assuming it picks uniformly between
+// the moves that don't kill it outright.
+pub struct Threat {
+    pub name: String,
+    pub length: u32,
+    squares: HashMap<Coord, f64>,
+}
+
+impl Threat {
+    pub fn probability(&self, coord: &Coord) -> f64 {
+        self.squares.get(coord).copied().unwrap_or_default()
+    }
+}
+
+// Squares a head can't move into over the next couple of turns. Tails are left out since they
+// move out of the way, which is close enough for a two turn lookahead.
+pub fn blocked_squares(board: &Board, walls: &HashSet<Coord>) -> HashSet<Coord> {
+    board
+        .snakes
+        .iter()
+        .flat_map(|x| x.body.split_last().map_or(&[][..], |(_, rest)| rest))
+        .chain(walls)
+        .copied()
+        .collect()
+}
+
+pub fn threats(board: &Board, you: &Battlesnake, walls: &HashSet<Coord>) -> Vec<Threat> {
+    let blocked = blocked_squares(board, walls);
+    let moves = |from: &Coord, previous: &Coord| -> Vec<Coord> {
+        get_adjacent_nodes(from)
+            .into_iter()
+            .map(|x| x.coord)
+            .filter(|x| board.contains(x) && !blocked.contains(x) && x != previous)
+            .collect()
+    };
+
+    board
+        .snakes
+        .iter()
+        .filter(|x| x.id != you.id)
+        .map(|opponent| {
+            let mut squares = HashMap::new();
+            let next_turn = moves(&opponent.head, &opponent.head);
+            for coord in &next_turn {
+                let probability = 1.0 / next_turn.len() as f64;
+                let turn_after = moves(coord, &opponent.head);
+                for next in &turn_after {
+                    *squares.entry(*next).or_default() += probability / turn_after.len() as f64;
+                }
+            }
+            Threat {
+                name: opponent.name.to_owned(),
+                length: opponent.length,
+                squares,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn threats_spread_over_safe_moves() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 5,
+            "width": 5,
+            "food": [],
+            "hazards": [],
+            "snakes": [
+              {
+                "id": "other-snake",
+                "name": "Other Snake",
+                "health": 54,
+                "body": [{"x": 0, "y": 0}, {"x": 0, "y": 1}, {"x": 0, "y": 2}],
+                "latency": "111",
+                "head": {"x": 0, "y": 0},
+                "length": 3
+              }
+            ]
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 54,
+            "body": [{"x": 4, "y": 4}],
+            "latency": "111",
+            "head": {"x": 4, "y": 4},
+            "length": 1
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+
+    let threats = threats(&state.board, &state.you, &HashSet::new());
+    assert_eq!(threats.len(), 1);
+    // The only way out of the corner is right, then up or right again
+    assert_eq!(threats[0].probability(&Coord { x: 1, y: 0 }), 0.0);
+    assert_eq!(threats[0].probability(&Coord { x: 2, y: 0 }), 0.5);
+    assert_eq!(threats[0].probability(&Coord { x: 1, y: 1 }), 0.5);
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::movement_set::Movement;
+
+// Tables are kept between turns of a game, since most of what we searched last turn is still
+// ahead of us. Games that never send `/end` would keep theirs forever, so only this many are kept.
+const KEPT_TABLES: usize = 32;
+
+// A game ID and one of our snake IDs in it. Two of our snakes in the same game each search from
+// their own point of view, so they can't share a table.
+type TableKey = (String, String);
+
+struct KeptTable {
+    table: TranspositionTable,
+    last_used: Instant,
+}
+
+static TABLES: Mutex<BTreeMap<TableKey, KeptTable>> = Mutex::new(BTreeMap::new());
+
+// Whether a stored score is exact, or only a bound because the search was cut off early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // The position is worth at least this much
+    Lower,
+    // The position is worth at most this much
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best: Option<Movement>,
+    generation: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    pub overwrites: u64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / self.probes as f64
+    }
+}
+
+// Search results for positions we've already seen, indexed by their Zobrist hash. The table has
+// a fixed number of slots, and when two positions want the same slot the one searched deeper
+// wins, unless the other is left over from an earlier search.
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u32,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    // The size is rounded down to a power of two so slots can be picked with a mask.
+    pub fn new(size: usize) -> TranspositionTable {
+        let size = match size {
+            0 => 1,
+            x => 1 << (usize::BITS - 1 - x.leading_zeros()),
+        };
+        TranspositionTable {
+            entries: vec![None; size],
+            generation: 0,
+            stats: TableStats::default(),
+        }
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    // Starts counting stats afresh, and marks everything stored so far as stale.
+    pub fn new_search(&mut self) {
+        self.generation += 1;
+        self.stats = TableStats::default();
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<Entry> {
+        self.stats.probes += 1;
+        let entry = self.entries[self.slot(key)].filter(|x| x.key == key)?;
+        self.stats.hits += 1;
+        Some(entry)
+    }
+
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: i32,
+        bound: Bound,
+        best: Option<Movement>,
+    ) {
+        let slot = self.slot(key);
+        let generation = self.generation;
+        if let Some(existing) = &self.entries[slot] {
+            let replace =
+                existing.key == key || existing.generation != generation || depth >= existing.depth;
+            if !replace {
+                return;
+            }
+            if existing.key != key {
+                self.stats.overwrites += 1;
+            }
+        }
+        self.stats.stores += 1;
+        self.entries[slot] = Some(Entry {
+            key,
+            depth,
+            score,
+            bound,
+            best,
+            generation,
+        });
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    pub fn log_stats(&self) {
+        let stats = self.stats();
+        let used = self.entries.iter().filter(|x| x.is_some()).count();
+        info!(
+            "Transposition table: {} probes, {:.1}% hits, {} stores, {} overwrites, {}/{} slots used",
+            stats.probes,
+            stats.hit_rate() * 100.0,
+            stats.stores,
+            stats.overwrites,
+            used,
+            self.entries.len()
+        );
+    }
+}
+
+// The table `snake_id` left over from last turn of `game_id`, or a new one if there isn't one of
+// `size`.
+pub fn take_table(game_id: &str, snake_id: &str, size: usize) -> TranspositionTable {
+    let kept = TABLES
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .remove(&(game_id.to_string(), snake_id.to_string()))
+        .map(|x| x.table);
+    let table = TranspositionTable::new(size);
+    match kept {
+        Some(kept) if kept.size() == table.size() => kept,
+        _ => table,
+    }
+}
+
+pub fn keep_table(game_id: &str, snake_id: &str, table: TranspositionTable) {
+    let mut tables = TABLES.lock().unwrap_or_else(|x| x.into_inner());
+    tables.insert(
+        (game_id.to_string(), snake_id.to_string()),
+        KeptTable {
+            table,
+            last_used: Instant::now(),
+        },
+    );
+    while tables.len() > KEPT_TABLES {
+        let oldest = tables
+            .iter()
+            .min_by_key(|(_, x)| x.last_used)
+            .map(|(key, _)| key.to_owned());
+        match oldest {
+            Some(key) => tables.remove(&key),
+            None => break,
+        };
+    }
+}
+
+pub fn forget(game_id: &str, snake_id: &str) {
+    TABLES
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .remove(&(game_id.to_string(), snake_id.to_string()));
+}
+
+#[test]
+fn table_prefers_deeper_entries_from_the_same_search() {
+    let mut table = TranspositionTable::new(6);
+    // Rounded down to 4 slots, so 1 and 5 share a slot
+    assert_eq!(table.entries.len(), 4);
+
+    table.store(1, 3, 10, Bound::Exact, Some(Movement::Up));
+    table.store(5, 2, 20, Bound::Exact, None);
+    assert_eq!(table.probe(1).map(|x| x.score), Some(10));
+    assert!(table.probe(5).is_none());
+
+    table.store(5, 4, 30, Bound::Lower, None);
+    assert!(table.probe(1).is_none());
+    assert_eq!(table.probe(5).map(|x| x.bound), Some(Bound::Lower));
+    assert_eq!(
+        table.stats(),
+        TableStats {
+            probes: 4,
+            hits: 2,
+            stores: 2,
+            overwrites: 1,
+        }
+    );
+
+    // Anything left over from an earlier search gives way
+    table.new_search();
+    table.store(1, 1, 40, Bound::Upper, None);
+    assert_eq!(table.probe(1).map(|x| x.score), Some(40));
+}
+
+#[test]
+fn tables_are_kept_per_game_and_snake() {
+    let mut table = take_table("kept-game", "first-snake", 16);
+    table.store(7, 2, 50, Bound::Exact, Some(Movement::Left));
+    keep_table("kept-game", "first-snake", table);
+    // Another of our snakes in the same game starts afresh
+    assert!(take_table("kept-game", "second-snake", 16)
+        .probe(7)
+        .is_none());
+    let mut table = take_table("kept-game", "first-snake", 16);
+    assert_eq!(table.probe(7).and_then(|x| x.best), Some(Movement::Left));
+
+    keep_table("kept-game", "first-snake", table);
+    forget("kept-game", "first-snake");
+    assert!(take_table("kept-game", "first-snake", 16)
+        .probe(7)
+        .is_none());
+
+    // A different size means a different configuration, so nothing is carried over
+    let mut table = take_table("kept-game", "first-snake", 16);
+    table.store(7, 2, 50, Bound::Exact, Some(Movement::Left));
+    keep_table("kept-game", "first-snake", table);
+    assert!(take_table("kept-game", "first-snake", 32)
+        .probe(7)
+        .is_none());
+}
@@ -0,0 +1,159 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    hazard::HazardLayer,
+    request::Coord,
+    simulation::{SimSnake, SimState},
+};
+
+#[cfg(test)]
+use crate::{movement_set::Movement, request::GameState};
+
+// Keys come from a fixed seed so the same position hashes the same way on every request.
+const SEED: u64 = 0x5eed_b0a7_d5a4_e000;
+// Health is hashed in buckets rather than exactly, so positions that only differ by a few points
+// of health share an entry.
+const HEALTH_BUCKET: u32 = 10;
+const HEALTH_BUCKETS: usize = (100 / HEALTH_BUCKET + 1) as usize;
+// Hazards stacked deeper than this hash the same, since they're deadly anyway.
+const MAX_STACKS: u32 = 4;
+
+struct SnakeKeys {
+    body: Vec<u64>,
+    head: Vec<u64>,
+    tail: Vec<u64>,
+    length: Vec<u64>,
+    health: Vec<u64>,
+}
+
+// Hashes positions by XORing a random key for every feature of the board, so positions reached
+// by different move orders land on the same transposition table entry. Hazards don't change
+// during a search, so their part of the hash is worked out once up front.
+pub struct Zobrist {
+    width: u32,
+    squares: usize,
+    snakes: Vec<SnakeKeys>,
+    food: Vec<u64>,
+    hazards: u64,
+}
+
+impl Zobrist {
+    pub fn new(state: &SimState, hazards: &HazardLayer) -> Zobrist {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let squares = (state.width * state.height) as usize;
+        let mut keys = |n: usize| -> Vec<u64> { (0..n).map(|_| rng.gen()).collect() };
+
+        let food = keys(squares);
+        let hazard_keys = keys(squares * MAX_STACKS as usize);
+        let snakes = state
+            .snakes
+            .iter()
+            .map(|_| SnakeKeys {
+                body: keys(squares),
+                head: keys(squares),
+                tail: keys(squares),
+                // Bodies can't be longer than the board, plus one for a tail doubled up after eating
+                length: keys(squares + 2),
+                health: keys(HEALTH_BUCKETS),
+            })
+            .collect();
+
+        let mut zobrist = Zobrist {
+            width: state.width,
+            squares,
+            snakes,
+            food,
+            hazards: 0,
+        };
+        for x in 0..state.width as i32 {
+            for y in 0..state.height as i32 {
+                let coord = Coord { x, y };
+                let stacks = hazards.stacks(&coord).min(MAX_STACKS);
+                if stacks > 0 {
+                    let index = zobrist.index(&coord) * MAX_STACKS as usize + stacks as usize - 1;
+                    zobrist.hazards ^= hazard_keys[index];
+                }
+            }
+        }
+        zobrist
+    }
+
+    fn index(&self, coord: &Coord) -> usize {
+        (coord.y * self.width as i32 + coord.x) as usize
+    }
+
+    pub fn hash(&self, state: &SimState) -> u64 {
+        let mut hash = self.hazards;
+        for food in &state.food {
+            hash ^= self.food[self.index(food)];
+        }
+        for (snake, keys) in state.snakes.iter().zip(&self.snakes) {
+            if snake.alive {
+                hash ^= self.hash_snake(snake, keys);
+            }
+        }
+        hash
+    }
+
+    // A doubled up tail cancels itself out of the body keys, which the length and tail keys make
+    // up for.
+    fn hash_snake(&self, snake: &SimSnake, keys: &SnakeKeys) -> u64 {
+        let mut hash = 0;
+        for segment in &snake.body {
+            hash ^= keys.body[self.index(segment)];
+        }
+        if let Some(tail) = snake.body.back() {
+            hash ^= keys.tail[self.index(tail)];
+        }
+        hash ^= keys.head[self.index(&snake.head())];
+        hash ^= keys.length[snake.body.len().min(self.squares + 1)];
+        hash ^= keys.health[(snake.health.min(100) / HEALTH_BUCKET) as usize];
+        hash
+    }
+}
+
+#[test]
+fn hash_ignores_move_order() {
+    let state: GameState = serde_json::from_str(
+        r#"{
+          "game": {"id": "unique-game-id", "ruleset": {"name": "standard"}, "timeout": 500},
+          "turn": 0,
+          "board": {
+            "height": 7,
+            "width": 7,
+            "food": [{"x": 6, "y": 6}],
+            "hazards": [{"x": 0, "y": 6}],
+            "snakes": []
+          },
+          "you": {
+            "id": "my-snake",
+            "name": "My Snake",
+            "health": 90,
+            "body": [{"x": 3, "y": 3}],
+            "latency": "111",
+            "head": {"x": 3, "y": 3},
+            "length": 1
+          }
+        }"#,
+    )
+    .expect("failed to parse game state");
+    let hazards = HazardLayer::new(&state.board, 14);
+    let start = SimState::new(&state.board, &state.you);
+    let zobrist = Zobrist::new(&start, &hazards);
+
+    let mut up_right = start.clone();
+    up_right.advance(&[Movement::Up], &hazards);
+    up_right.advance(&[Movement::Right], &hazards);
+    let mut right_up = start.clone();
+    right_up.advance(&[Movement::Right], &hazards);
+    right_up.advance(&[Movement::Up], &hazards);
+    assert_eq!(zobrist.hash(&up_right), zobrist.hash(&right_up));
+    assert_ne!(zobrist.hash(&start), zobrist.hash(&up_right));
+
+    // Health within the same bucket hashes the same
+    let mut hungrier = up_right.clone();
+    hungrier.snakes[0].health -= 1;
+    assert_eq!(zobrist.hash(&up_right), zobrist.hash(&hungrier));
+    hungrier.snakes[0].health -= HEALTH_BUCKET;
+    assert_ne!(zobrist.hash(&up_right), zobrist.hash(&hungrier));
+}